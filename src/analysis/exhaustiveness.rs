@@ -0,0 +1,206 @@
+use std::fmt::Display;
+
+use super::types::Type;
+
+/// One of the ways a value can be built, which a pattern can test for
+#[derive(Debug, PartialEq, Clone)]
+pub enum Constructor {
+    Variant {
+        tag: usize,
+        name: String,
+    },
+    Bool(bool),
+    Integer(i32),
+    String(String),
+}
+
+impl Display for Constructor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Constructor::Variant { tag: _, name } => write!(f, "{}", name),
+            Constructor::Bool(value) => write!(f, "{}", value),
+            Constructor::Integer(value) => write!(f, "{}", value),
+            Constructor::String(value) => write!(f, "{:?}", value),
+        }
+    }
+}
+
+/// A pattern reduced to what matters for exhaustiveness, bindings are the same as `_`
+#[derive(Debug, Clone)]
+pub enum Pat {
+    Wild,
+    Constructor(Constructor, Vec<Pat>),
+}
+
+impl Display for Pat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pat::Wild => write!(f, "_"),
+            Pat::Constructor(constructor, fields) => {
+                if fields.is_empty() {
+                    return write!(f, "{}", constructor);
+                }
+                let fields: Vec<String> = fields
+                    .iter()
+                    .map(|field| field.to_string())
+                    .collect();
+                write!(f, "{}({})", constructor, fields.join(", "))
+            }
+        }
+    }
+}
+
+/// Checks match arms against each other, following "Warnings for pattern matching"
+/// by Luc Maranget. `signature` lists every constructor of a type along with the
+/// types of its fields, or returns `None` for types with too many to list, like `int`
+pub struct Checker<'s> {
+    signature: &'s dyn Fn(&Type) -> Option<Vec<(Constructor, Vec<Type>)>>,
+}
+
+impl<'s> Checker<'s> {
+    pub fn new(signature: &'s dyn Fn(&Type) -> Option<Vec<(Constructor, Vec<Type>)>>) -> Self {
+        Checker { signature }
+    }
+
+    /// Returns true if `row` matches some value that none of the `rows` before it do
+    pub fn is_useful(&self, rows: &Vec<Vec<Pat>>, row: &Vec<Pat>, types: &Vec<Type>) -> bool {
+        if row.is_empty() {
+            return rows.is_empty();
+        }
+
+        match &row[0] {
+            Pat::Constructor(constructor, fields) => {
+                let field_types = self.field_types(&types[0], constructor, fields.len());
+                let rows = Self::specialize(rows, constructor, fields.len());
+                let row = [fields.clone(), row[1..].to_vec()].concat();
+                let types = [field_types, types[1..].to_vec()].concat();
+                return self.is_useful(&rows, &row, &types);
+            }
+            Pat::Wild => {
+                match self.complete_signature(rows, &types[0]) {
+                    Some(signature) => {
+                        signature.into_iter().any(|(constructor, field_types)| {
+                            let arity = field_types.len();
+                            let rows = Self::specialize(rows, &constructor, arity);
+                            let row = [vec![Pat::Wild; arity], row[1..].to_vec()].concat();
+                            let types = [field_types, types[1..].to_vec()].concat();
+                            self.is_useful(&rows, &row, &types)
+                        })
+                    }
+                    None => {
+                        let rows = Self::default(rows);
+                        self.is_useful(&rows, &row[1..].to_vec(), &types[1..].to_vec())
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns a value that none of the `rows` match, if there is one
+    pub fn missing(&self, rows: &Vec<Vec<Pat>>, types: &Vec<Type>) -> Option<Vec<Pat>> {
+        if types.is_empty() {
+            return match rows.is_empty() {
+                true => Some(vec![]),
+                false => None,
+            };
+        }
+
+        if let Some(signature) = self.complete_signature(rows, &types[0]) {
+            for (constructor, field_types) in signature {
+                let arity = field_types.len();
+                let rows = Self::specialize(rows, &constructor, arity);
+                let types = [field_types, types[1..].to_vec()].concat();
+                if let Some(mut witness) = self.missing(&rows, &types) {
+                    let rest = witness.split_off(arity);
+                    let head = Pat::Constructor(constructor, witness);
+                    return Some([vec![head], rest].concat());
+                }
+            }
+            return None;
+        }
+
+        let mut witness = self.missing(&Self::default(rows), &types[1..].to_vec())?;
+
+        // Name a constructor that isn't matched if there is one, it reads better than `_`
+        let used = Self::head_constructors(rows);
+        let head = match (self.signature)(&types[0]) {
+            Some(signature) if !used.is_empty() => {
+                signature
+                    .into_iter()
+                    .find(|(constructor, _)| !used.contains(constructor))
+                    .map(|(constructor, field_types)| {
+                        Pat::Constructor(constructor, vec![Pat::Wild; field_types.len()])
+                    })
+                    .unwrap_or(Pat::Wild)
+            }
+            _ => Pat::Wild,
+        };
+        witness.insert(0, head);
+        return Some(witness);
+    }
+
+    /// Returns the signature of `typ` if every one of its constructors begins one of the `rows`
+    fn complete_signature(
+        &self,
+        rows: &Vec<Vec<Pat>>,
+        typ: &Type
+    ) -> Option<Vec<(Constructor, Vec<Type>)>> {
+        let signature = (self.signature)(typ)?;
+        let used = Self::head_constructors(rows);
+        if signature.iter().all(|(constructor, _)| used.contains(constructor)) {
+            return Some(signature);
+        }
+        return None;
+    }
+
+    fn field_types(&self, typ: &Type, constructor: &Constructor, arity: usize) -> Vec<Type> {
+        let field_types = (self.signature)(typ).and_then(|signature| {
+            signature
+                .into_iter()
+                .find(|(other, _)| other == constructor)
+                .map(|(_, field_types)| field_types)
+        });
+        match field_types {
+            Some(field_types) if field_types.len() == arity => field_types,
+            _ => vec![Type::Error; arity],
+        }
+    }
+
+    fn head_constructors(rows: &Vec<Vec<Pat>>) -> Vec<Constructor> {
+        let mut constructors: Vec<Constructor> = vec![];
+        for row in rows {
+            if let Some(Pat::Constructor(constructor, _)) = row.first() {
+                if !constructors.contains(constructor) {
+                    constructors.push(constructor.clone());
+                }
+            }
+        }
+        return constructors;
+    }
+
+    /// Keeps the rows that match `constructor`, replacing their first pattern with its fields
+    fn specialize(rows: &Vec<Vec<Pat>>, constructor: &Constructor, arity: usize) -> Vec<Vec<Pat>> {
+        let mut specialized: Vec<Vec<Pat>> = vec![];
+        for row in rows {
+            match &row[0] {
+                Pat::Constructor(other, fields) if other == constructor => {
+                    specialized.push([fields.clone(), row[1..].to_vec()].concat());
+                }
+                Pat::Constructor(_, _) => {}
+                Pat::Wild => {
+                    specialized.push([vec![Pat::Wild; arity], row[1..].to_vec()].concat());
+                }
+            }
+        }
+        return specialized;
+    }
+
+    /// Keeps the rows that match anything, without their first pattern
+    fn default(rows: &Vec<Vec<Pat>>) -> Vec<Vec<Pat>> {
+        return rows
+            .iter()
+            .filter(|row| matches!(row[0], Pat::Wild))
+            .map(|row| row[1..].to_vec())
+            .collect();
+    }
+}
@@ -1 +1,3 @@
 pub mod name_resolution;
+pub mod types;
+pub mod exhaustiveness;
@@ -1,144 +1,1761 @@
 use std::{ collections::HashMap, vec };
 
-use crate::{ ast::{ Expr, Stmt }, errors::{ self, CompilerError } };
+use crate::{
+    ast::{ AstOp, Expr, Field, Pattern, Span, Stmt, TypeExpr, Variant },
+    errors::{ self, CompilerError },
+};
 
-#[derive(Debug, PartialEq, Eq)]
-enum Type {
-    None,
-    Integer,
-    String,
-}
+use super::{
+    exhaustiveness::{ Checker, Constructor, Pat },
+    types::{ Mismatch, Substitution, Type },
+};
 
 struct Symbol<'a> {
     name: &'a String,
     mutable: bool,
     typ: Type,
+    /// Type variables in `typ` that are replaced with fresh ones at every use, which is
+    /// what lets a function like `function id(x) { return x }` be called with any type
+    generics: Vec<usize>,
+    /// Where the symbol's name is declared
+    span: Span,
+    /// Whether the symbol has been read anywhere
+    read: bool,
+    /// Whether the symbol has been reassigned with `->`
+    reassigned: bool,
+}
+
+impl<'a> Symbol<'a> {
+    /// Produces the warnings for a symbol going out of scope without being used, unless
+    /// its name starts with `_`
+    fn check_usage(&self) -> Vec<CompilerError> {
+        let mut warnings: Vec<CompilerError> = vec![];
+        if self.name.starts_with('_') {
+            return warnings;
+        }
+
+        let span = self.span;
+        if !self.read {
+            warnings.push(
+                CompilerError::new(
+                    errors::Kind::UnusedBinding,
+                    errors::Flag::Warning,
+                    span.start,
+                    span.stop - span.start,
+                    format!(
+                        "'{}' is never used, name it '_{}' if this is intended",
+                        self.name,
+                        self.name
+                    ).as_str()
+                )
+            );
+        }
+        if self.mutable && !self.reassigned {
+            warnings.push(
+                CompilerError::new(
+                    errors::Kind::UnusedMut,
+                    errors::Flag::Warning,
+                    span.start,
+                    span.stop - span.start,
+                    format!("'{}' is declared 'mut' but is never reassigned", self.name).as_str()
+                )
+            );
+            warnings.push(
+                CompilerError::new(
+                    errors::Kind::UnusedMut,
+                    errors::Flag::Suggestion,
+                    span.start,
+                    span.stop - span.start,
+                    format!("remove 'mut' to declare '{}' as immutable", self.name).as_str()
+                )
+            );
+        }
+        return warnings;
+    }
+}
+
+/// A user defined struct type
+struct StructDef<'a> {
+    name: &'a String,
+    span: Span,
+    /// Each field in the order it is laid out in memory
+    fields: Vec<(&'a String, Type)>,
+}
+
+/// A user defined enum type
+struct EnumDef<'a> {
+    name: &'a String,
+    span: Span,
+    /// Each variant, whose tag is its position here
+    variants: Vec<VariantDef<'a>>,
+}
+
+struct VariantDef<'a> {
+    name: &'a String,
+    span: Span,
+    payload: Vec<Type>,
 }
 
 struct Context<'a> {
     pub symbols: HashMap<&'a String, Symbol<'a>>,
+    /// Bindings further down in this scope that haven't been reached yet, and where they are
+    pub pending: HashMap<&'a String, Span>,
+    /// Every struct type declared in this scope, in the order they were declared
+    structs: Vec<StructDef<'a>>,
+    /// Every enum type declared in this scope, in the order they were declared
+    enums: Vec<EnumDef<'a>>,
 }
 
 impl<'a> Context<'a> {
     pub fn new() -> Self {
         Context {
             symbols: HashMap::new(),
+            pending: HashMap::new(),
+            structs: vec![],
+            enums: vec![],
         }
     }
 
-    pub fn load(&mut self, key: &'a String, mutable: bool, typ: Type) -> bool {
-        return self.symbols.insert(key, Symbol { name: key, mutable, typ }).is_some();
+    /// Loads a symbol, returning the symbol it replaces if there was one
+    pub fn load(
+        &mut self,
+        key: &'a String,
+        mutable: bool,
+        typ: Type,
+        span: Span
+    ) -> Option<Symbol<'a>> {
+        let symbol = Symbol {
+            name: key,
+            mutable,
+            typ,
+            generics: vec![],
+            span,
+            read: false,
+            reassigned: false,
+        };
+        return self.symbols.insert(key, symbol);
     }
 
-    pub fn check(&self, key: &'a String) -> bool {
-        return self.symbols.get(key).is_some();
+    /// Records every binding declared directly in `body`, so that using one too early can
+    /// be reported as such rather than as an unknown symbol
+    pub fn predeclare(&mut self, body: &'a Vec<Stmt>) {
+        for stmt in body {
+            if let Stmt::Binding {
+                span,
+                mutable: _,
+                name,
+                name_span: _,
+                initializer: _,
+                annotation: _,
+            } = stmt {
+                self.pending.entry(name).or_insert(*span);
+            }
+        }
     }
 }
 
 pub struct Resolver<'a> {
     pub errors: Vec<CompilerError>,
-    global: Vec<Context<'a>>,
-    current_ctx: usize,
-    // (TODO) keep track of user defined types?
+    /// Stack of lexical scopes, the first being the global scope and the last the innermost
+    scopes: Vec<Context<'a>>,
+    /// Where each binding whose scope has ended was declared, for better diagnostics
+    expired: HashMap<&'a String, Span>,
+    /// Everything inferred about type variables so far
+    substitution: Substitution,
+    /// The return type of each function being resolved
+    returns: Vec<Type>,
+    /// Where the binding whose initializer is being resolved is declared
+    initializing: Option<Span>,
+    loops: usize,
+    /// The position of the field that each field access or struct literal field refers to,
+    /// keyed by the span of the access or of the literal's field name, so the compiler can
+    /// lay them out
+    pub offsets: HashMap<Span, usize>,
+    /// The tag of the variant that each variant constructor or pattern refers to, keyed
+    /// by its span
+    pub tags: HashMap<Span, usize>,
 }
 
 impl<'a> Resolver<'a> {
     pub fn new() -> Self {
         Resolver {
             errors: vec![],
-            global: vec![Context::new()],
-            current_ctx: 0usize,
+            scopes: vec![Context::new()],
+            expired: HashMap::new(),
+            substitution: Substitution::new(),
+            returns: vec![],
+            initializing: None,
+            loops: 0usize,
+            offsets: HashMap::new(),
+            tags: HashMap::new(),
+        }
+    }
+
+    /// Returns every struct type in scope, innermost first
+    fn structs(&self) -> impl Iterator<Item = &StructDef<'a>> {
+        return self.scopes.iter().rev().flat_map(|ctx| ctx.structs.iter().rev());
+    }
+
+    /// Returns every enum type in scope, innermost first
+    fn enums(&self) -> impl Iterator<Item = &EnumDef<'a>> {
+        return self.scopes.iter().rev().flat_map(|ctx| ctx.enums.iter().rev());
+    }
+
+    fn find_struct(&self, name: &String) -> Option<&StructDef<'a>> {
+        return self.structs().find(|def| def.name == name);
+    }
+
+    fn find_enum(&self, name: &String) -> Option<&EnumDef<'a>> {
+        return self.enums().find(|def| def.name == name);
+    }
+
+    /// Returns the enum a variant belongs to along with its tag
+    fn find_variant(&self, name: &String) -> Option<(&EnumDef<'a>, usize)> {
+        return self.enums().find_map(|def| {
+            def.variants
+                .iter()
+                .position(|variant| variant.name == name)
+                .map(|tag| (def, tag))
+        });
+    }
+
+    /// Reports a name that has already been taken by another declaration of the same
+    /// kind, returning whether it was free
+    fn check_declared_name(&mut self, span: Span, name: &String, previous: Option<Span>) -> bool {
+        let previous = match previous {
+            Some(previous) => previous,
+            None => {
+                return true;
+            }
+        };
+        self.errors.push(
+            CompilerError::new(
+                errors::Kind::NameError,
+                errors::Flag::Abort,
+                span.start,
+                span.stop - span.start,
+                format!("'{}' has already been declared", name).as_str()
+            ).with_label(
+                previous.start,
+                previous.stop - previous.start,
+                "it was first declared here"
+            )
+        );
+        return false;
+    }
+
+    /// Registers a struct or enum in the innermost scope so that it can be referred to
+    /// before it is resolved. A type can't shadow another one that is in scope
+    fn declare_type(&mut self, stmt: &'a Stmt) {
+        let (span, name) = match stmt {
+            | Stmt::Struct { span, name, fields: _ }
+            | Stmt::Enum { span, name, variants: _ } => (*span, name),
+            _ => {
+                return;
+            }
+        };
+
+        let previous = self
+            .find_struct(name)
+            .map(|def| def.span)
+            .or(self.find_enum(name).map(|def| def.span));
+        if !self.check_declared_name(span, name, previous) {
+            return;
+        }
+
+        match stmt {
+            Stmt::Enum { span: _, name: _, variants } => {
+                // Variants are referred to by name alone, so they must be unique across enums
+                let mut declared: Vec<VariantDef<'a>> = vec![];
+                for variant in variants {
+                    let previous = self
+                        .find_variant(&variant.name)
+                        .map(|(def, tag)| def.variants[tag].span)
+                        .or(
+                            declared
+                                .iter()
+                                .find(|other| other.name == &variant.name)
+                                .map(|other| other.span)
+                        );
+                    if self.check_declared_name(variant.span, &variant.name, previous) {
+                        let span = variant.span;
+                        declared.push(VariantDef { name: &variant.name, span, payload: vec![] });
+                    }
+                }
+                let ctx = self.scopes.last_mut().unwrap();
+                ctx.enums.push(EnumDef { name, span, variants: declared });
+            }
+            _ => {
+                let ctx = self.scopes.last_mut().unwrap();
+                ctx.structs.push(StructDef { name, span, fields: vec![] });
+            }
+        }
+    }
+
+    /// Resolves the field or payload types of a struct or enum registered by `declare_type`
+    fn define_type(&mut self, stmt: &'a Stmt) {
+        match stmt {
+            Stmt::Struct { span, name: _, fields } => self.resolve_struct_fields(*span, fields),
+            Stmt::Enum { span, name: _, variants } => self.resolve_enum_payloads(*span, variants),
+            _ => {}
+        }
+    }
+
+    /// Resolves the payload types of the enum declared at `span`
+    fn resolve_enum_payloads(&mut self, span: Span, variants: &'a Vec<Variant>) {
+        let mut payloads: Vec<(Span, Vec<Type>)> = vec![];
+        for variant in variants {
+            let payload = variant.payload
+                .iter()
+                .map(|annotation| self.resolve_type(annotation))
+                .collect();
+            payloads.push((variant.span, payload));
+        }
+
+        let ctx = self.scopes.last_mut().unwrap();
+        if let Some(def) = ctx.enums.iter_mut().find(|def| def.span == span) {
+            for (span, payload) in payloads {
+                let variant = def.variants.iter_mut().find(|variant| variant.span == span);
+                if let Some(variant) = variant {
+                    variant.payload = payload;
+                }
+            }
         }
     }
 
-    pub fn resolve_symbol(&mut self, expr: &'a Expr) -> bool {
+    /// Resolves the field types of the struct declared at `span`
+    fn resolve_struct_fields(&mut self, span: Span, fields: &'a Vec<Field>) {
+        let mut resolved: Vec<(&'a String, Type)> = vec![];
+        for field in fields {
+            if resolved.iter().any(|(name, _)| *name == &field.name) {
+                self.errors.push(
+                    CompilerError::new(
+                        errors::Kind::NameError,
+                        errors::Flag::Abort,
+                        field.span.start,
+                        field.span.stop - field.span.start,
+                        format!("the field '{}' is declared more than once", field.name).as_str()
+                    )
+                );
+                continue;
+            }
+            let typ = self.resolve_type(&field.annotation);
+            resolved.push((&field.name, typ));
+        }
+
+        let ctx = self.scopes.last_mut().unwrap();
+        if let Some(def) = ctx.structs.iter_mut().find(|def| def.span == span) {
+            def.fields = resolved;
+        }
+    }
+
+    /// Enters a new scope containing the bindings of `body`
+    fn enter_scope(&mut self, body: &'a Vec<Stmt>) {
+        let mut ctx = Context::new();
+        ctx.predeclare(body);
+        self.scopes.push(ctx);
+        self.hoist(body);
+    }
+
+    /// Declares every function in `body` before anything in it is resolved, so that
+    /// functions can call each other no matter which one comes first
+    fn hoist(&mut self, body: &'a Vec<Stmt>) {
+        for stmt in body {
+            if let Stmt::Function { span: _, name, name_span, parameters, body: _ } = stmt {
+                let parameter_types: Vec<Type> = parameters
+                    .iter()
+                    .map(|_| self.substitution.fresh())
+                    .collect();
+                let returns = self.substitution.fresh();
+                let typ = Type::Function(parameter_types, Box::new(returns));
+                self.declare(name, false, typ, *name_span);
+            }
+        }
+    }
+
+    fn exit_scope(&mut self) {
+        let ctx = self.scopes.pop().expect("exited the global scope");
+        let mut symbols: Vec<Symbol<'a>> = ctx.symbols.into_values().collect();
+        symbols.sort_by_key(|symbol| symbol.span.start);
+        for symbol in symbols {
+            self.errors.extend(symbol.check_usage());
+            self.expired.insert(symbol.name, symbol.span);
+        }
+    }
+
+    /// Loads a symbol into the innermost scope, shadowing any symbol of the same name
+    /// from an enclosing scope. Only a mutable binding can be redeclared in the same scope
+    fn declare(&mut self, name: &'a String, mutable: bool, typ: Type, span: Span) {
+        let ctx = self.scopes.last_mut().unwrap();
+        ctx.pending.remove(name);
+
+        let previous = ctx.symbols
+            .get(name)
+            .filter(|symbol| !symbol.mutable)
+            .map(|symbol| symbol.span);
+        if let Some(previous) = previous {
+            self.errors.push(
+                CompilerError::new(
+                    errors::Kind::NameError,
+                    errors::Flag::Abort,
+                    span.start,
+                    span.stop - span.start,
+                    format!(
+                        "'{}' is already defined in this scope and cannot be redeclared",
+                        name
+                    ).as_str()
+                ).with_label(
+                    previous.start,
+                    previous.stop - previous.start,
+                    "it was first declared here"
+                )
+            );
+            return;
+        }
+
+        // A mutable binding that is redeclared goes out of scope here
+        if let Some(replaced) = self.scopes.last_mut().unwrap().load(name, mutable, typ, span) {
+            self.errors.extend(replaced.check_usage());
+        }
+    }
+
+    /// Returns the innermost symbol with this name, if there is one in scope
+    fn lookup_mut(&mut self, name: &String) -> Option<&mut Symbol<'a>> {
+        return self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|ctx| ctx.symbols.get_mut(name));
+    }
+
+    /// Returns true if every path through `body` ends in a `return`
+    fn always_returns(body: &[Stmt]) -> bool {
+        return body.iter().any(|stmt| {
+            match stmt {
+                Stmt::Return { span: _, value: _ } => true,
+                Stmt::Block { span: _, body } => Self::always_returns(body),
+                Stmt::If { span: _, condition: _, body, else_branch: Some(else_branch) } =>
+                    Self::always_returns(body) &&
+                        Self::always_returns(std::slice::from_ref(else_branch.as_ref())),
+                _ => false,
+            }
+        });
+    }
+
+    /// Turns the type variables of a function that nothing else in scope depends on
+    /// into generics, so that each call to the function can use different types
+    fn generalize(&mut self, name: &'a String, typ: &Type) {
+        let mut environment: Vec<usize> = vec![];
+        for ctx in &self.scopes {
+            for symbol in ctx.symbols.values() {
+                if std::ptr::eq(symbol.name, name) {
+                    continue;
+                }
+
+                let mut vars: Vec<usize> = vec![];
+                self.substitution.free_vars(&symbol.typ, &mut vars);
+                environment.extend(vars.into_iter().filter(|var| !symbol.generics.contains(var)));
+            }
+        }
+
+        let mut vars: Vec<usize> = vec![];
+        self.substitution.free_vars(typ, &mut vars);
+        let generics = vars
+            .into_iter()
+            .filter(|var| !environment.contains(var))
+            .collect();
+
+        // The type is kept unresolved so that a call can still cite what it was inferred from
+        if let Some(symbol) = self.scopes.last_mut().unwrap().symbols.get_mut(name) {
+            symbol.typ = typ.clone();
+            symbol.generics = generics;
+        }
+    }
+
+    /// Unifies two types, returning an error at `span` if they conflict. `message` describes
+    /// a plain mismatch between the two (resolved) types, and the error cites wherever
+    /// `expected` was inferred from
+    fn unify<F>(
+        &mut self,
+        expected: &Type,
+        found: &Type,
+        span: Span,
+        message: F
+    ) -> Result<(), CompilerError>
+        where F: FnOnce(&Type, &Type) -> String
+    {
+        let origin = self.substitution.origin(expected);
+        let mismatch = match self.substitution.unify(expected, found, span) {
+            Ok(()) => {
+                return Ok(());
+            }
+            Err(mismatch) => mismatch,
+        };
+
+        let error = match mismatch {
+            Mismatch::Types { expected, found } => {
+                let error = CompilerError::new(
+                    errors::Kind::TypeError,
+                    errors::Flag::Abort,
+                    span.start,
+                    span.stop - span.start,
+                    message(&expected, &found).as_str()
+                );
+                match origin {
+                    Some(origin) if origin != span =>
+                        error.with_label(
+                            origin.start,
+                            origin.stop - origin.start,
+                            format!("'{}' was inferred from this", expected).as_str()
+                        ),
+                    _ => error,
+                }
+            }
+            Mismatch::Infinite { var, typ } =>
+                CompilerError::new(
+                    errors::Kind::TypeError,
+                    errors::Flag::Abort,
+                    span.start,
+                    span.stop - span.start,
+                    format!(
+                        "this would create an infinite type, '{}' would have to be '{}'",
+                        var,
+                        typ
+                    ).as_str()
+                ),
+            Mismatch::Operator { op, typ, span: op_span } =>
+                CompilerError::new(
+                    errors::Kind::TypeError,
+                    errors::Flag::Abort,
+                    span.start,
+                    span.stop - span.start,
+                    format!("this is a '{}', which cannot be used with {}", typ, op).as_str()
+                ).with_label(
+                    op_span.start,
+                    op_span.stop - op_span.start,
+                    format!("{} is applied to it here", op).as_str()
+                ),
+        };
+        return Err(error);
+    }
+
+    /// Requires that `op` can be applied to values of `typ`, returning an error at `span`
+    /// if it can't be
+    fn constrain(&mut self, typ: &Type, op: AstOp, span: Span) -> Result<(), CompilerError> {
+        match self.substitution.constrain(typ, op, span) {
+            Ok(()) => Ok(()),
+            Err(_) =>
+                Err(
+                    CompilerError::new(
+                        errors::Kind::TypeError,
+                        errors::Flag::Abort,
+                        span.start,
+                        span.stop - span.start,
+                        format!(
+                            "cannot apply {} to a value of type '{}'",
+                            op,
+                            self.substitution.resolve(typ)
+                        ).as_str()
+                    )
+                ),
+        }
+    }
+
+    /// Looks up the type of a symbol, reporting an error if it isn't in scope
+    fn resolve_symbol(&mut self, span: &Span, name: &'a String) -> Type {
+        if let Some(symbol) = self.lookup_mut(name) {
+            symbol.read = true;
+            let generics = symbol.generics.clone();
+            let typ = symbol.typ.clone();
+            return self.substitution.instantiate(&generics, &typ);
+        }
+
+        let variant = self
+            .find_variant(name)
+            .map(|(def, tag)| (def.name.clone(), tag, def.variants[tag].payload.clone()));
+        if let Some((enum_name, tag, payload)) = variant {
+            self.tags.insert(*span, tag);
+            let typ = Type::Enum(enum_name);
+            if payload.is_empty() {
+                return typ;
+            }
+            return Type::Function(payload, Box::new(typ));
+        }
+
+        let pending = self.scopes
+            .iter()
+            .rev()
+            .find_map(|ctx| ctx.pending.get(name))
+            .copied();
+        if let Some(declared) = pending {
+            let message = match self.initializing {
+                Some(initializing) if initializing == declared =>
+                    format!("'{}' cannot be used in its own initializer", name),
+                _ => format!("'{}' is used before it is defined", name),
+            };
+            self.errors.push(
+                CompilerError::new(
+                    errors::Kind::NameError,
+                    errors::Flag::Abort,
+                    span.start,
+                    span.stop - span.start,
+                    message.as_str()
+                ).with_label(
+                    declared.start,
+                    declared.stop - declared.start,
+                    "it is defined here"
+                )
+            );
+        } else if let Some(declared) = self.expired.get(name) {
+            self.errors.push(
+                CompilerError::new(
+                    errors::Kind::NameError,
+                    errors::Flag::Abort,
+                    span.start,
+                    span.stop - span.start,
+                    format!("'{}' is not in scope here", name).as_str()
+                ).with_label(
+                    declared.start,
+                    declared.stop - declared.start,
+                    "it was declared here, in a block that has already ended"
+                )
+            );
+        } else {
+            self.errors.push(
+                CompilerError::new(
+                    errors::Kind::NameError,
+                    errors::Flag::Abort,
+                    span.start,
+                    span.stop - span.start,
+                    format!("unknown symbol '{}'", name).as_str()
+                )
+            );
+        }
+        return Type::Error;
+    }
+
+    /// Resolves the type named by an annotation
+    fn resolve_type(&mut self, annotation: &TypeExpr) -> Type {
+        match annotation {
+            TypeExpr::Empty { span: _ } => Type::Error,
+            TypeExpr::Named { span, name } => {
+                match name.as_str() {
+                    "int" => Type::Integer,
+                    "float" => Type::Float,
+                    "str" => Type::String,
+                    "bool" => Type::Bool,
+                    _ if self.find_struct(name).is_some() => Type::Struct(name.clone()),
+                    _ if self.find_enum(name).is_some() => Type::Enum(name.clone()),
+                    _ => {
+                        self.errors.push(
+                            CompilerError::new(
+                                errors::Kind::NameError,
+                                errors::Flag::Abort,
+                                span.start,
+                                span.stop - span.start,
+                                format!("unknown type '{}'", name).as_str()
+                            )
+                        );
+                        return Type::Error;
+                    }
+                }
+            }
+            TypeExpr::Array { span: _, element } => {
+                Type::Array(Box::new(self.resolve_type(element)))
+            }
+        }
+    }
+
+    /// Checks `xs[i] -> value` and `p.x -> value`, which require the array or struct
+    /// to belong to a mutable binding
+    fn resolve_member_assignment(
+        &mut self,
+        lhs: &'a Expr,
+        rhs: &'a Expr,
+        value_type: Type
+    ) -> Type {
+        let (part, container) = match lhs {
+            Expr::Field { span: _, target: _, name: _ } => ("a field", "a field of type"),
+            _ => ("an element", "an array of"),
+        };
+
+        let element_type = self.resolve_expr(lhs);
+        let result = self.unify(&element_type, &value_type, rhs.span(), |expected, found| {
+            format!("cannot store a '{}' in {} '{}'", found, container, expected)
+        });
+        if let Err(error) = result {
+            self.errors.push(error);
+        }
+
+        // Find the binding the value ultimately belongs to, e.g. `xs` in `xs[i].y`
+        let mut root = lhs;
+        loop {
+            match root {
+                | Expr::Index { span: _, target, index: _ }
+                | Expr::Field { span: _, target, name: _ } => {
+                    root = &**target;
+                }
+                _ => {
+                    break;
+                }
+            }
+        }
+
+        let (target, name) = match root {
+            Expr::Symbol { span, name } => (*span, name),
+            _ => {
+                return element_type;
+            }
+        };
+        let (mutable, declared) = match self.lookup_mut(name) {
+            Some(symbol) => {
+                symbol.reassigned = true;
+                (symbol.mutable, symbol.span)
+            }
+            None => {
+                return element_type;
+            }
+        };
+
+        if !mutable {
+            self.errors.push(
+                CompilerError::new(
+                    errors::Kind::NameError,
+                    errors::Flag::Abort,
+                    target.start,
+                    target.stop - target.start,
+                    format!(
+                        "cannot assign to {} of '{}' because it is not mutable",
+                        part,
+                        name
+                    ).as_str()
+                ).with_label(
+                    declared.start,
+                    declared.stop - declared.start,
+                    format!("'{}' was declared here, try 'mut {}' to make it mutable", name, name).as_str()
+                )
+            );
+        }
+        return element_type;
+    }
+
+    pub fn resolve_expr(&mut self, expr: &'a Expr) -> Type {
         match expr {
+            Expr::Integer { span: _, value: _ } => Type::Integer,
+            Expr::Float { span: _, value: _ } => Type::Float,
+            Expr::Bool { span: _, value: _ } => Type::Bool,
+            Expr::String { span: _, value: _ } => Type::String,
             Expr::Symbol { span, name } => {
-                if !self.global[self.current_ctx].check(name) {
+                let typ = self.resolve_symbol(span, name);
+
+                // The variant is built where its constructor is called, so a constructor
+                // that takes a payload can't be passed around like a function
+                if self.tags.contains_key(span) && matches!(typ, Type::Function(_, _)) {
                     self.errors.push(
                         CompilerError::new(
-                            errors::Kind::NameError,
+                            errors::Kind::TypeError,
                             errors::Flag::Abort,
-                            span.line,
                             span.start,
                             span.stop - span.start,
-                            format!("unknown symbol '{}'", name).as_str()
+                            format!("'{}' has a payload, so it can only be called", name).as_str()
                         )
                     );
+                    return Type::Error;
                 }
-                return false;
+                typ
             }
-            _ => true,
-        }
-    }
 
-    pub fn resolve_expr(&mut self, expr: &'a Expr) -> Type {
-        match expr {
-            // Expr::Symbol { span, name } => {
-            //     match name.as_str() {
-            //         "int" => Type::Integer,
-            //         "str" => Type::String,
-            //         _ => {
-            // self.errors.push(
-            //     CompilerError::new(
-            //         errors::Kind::NameError,
-            //         errors::Flag::Abort,
-            //         span.line,
-            //         span.start,
-            //         span.stop - span.start,
-            //         format!("unknown symbol '{}'", name).as_str()
-            //     )
-            // );
-            //             return Type::None;
-            //         }
-            //     }
-            // }
-            Expr::Integer { span: _, value: _ } => Type::Integer,
-            Expr::String { span: _, value: _ } => Type::String,
+            Expr::Binary { span, lhs, rhs, op } => {
+                let lhs_type = self.resolve_expr(&**lhs);
+                let rhs_type = self.resolve_expr(&**rhs);
 
-            Expr::Binary { span, lhs, rhs, op: _ } => {
-                // Check to see if these symbols exist
-                if !self.resolve_symbol(&**lhs) || !self.resolve_symbol(&**rhs) {
-                    return Type::None;
+                // Every binary operator requires both sides to be the same type
+                let same = self.unify(&lhs_type, &rhs_type, *span, |lhs, rhs| {
+                    format!("cannot apply {} to a '{}' and a '{}'", op, lhs, rhs)
+                });
+                if let Err(error) = same.and_then(|_| self.constrain(&lhs_type, *op, *span)) {
+                    self.errors.push(error);
+                    return Type::Error;
                 }
 
-                // Resolve the types of each node
-                let lhs_type = self.resolve_expr(&**lhs);
-                let rhs_type = self.resolve_expr(&**rhs);
-                if lhs_type == rhs_type {
-                    return lhs_type;
-                } else {
+                if op.is_comparison() {
+                    return Type::Bool;
+                }
+                return lhs_type;
+            }
+            Expr::Grouping { span: _, inner } => self.resolve_expr(&**inner),
+            Expr::Unary { span, op, operand } => {
+                let operand_type = self.resolve_expr(&**operand);
+                if let Err(error) = self.constrain(&operand_type, *op, *span) {
+                    self.errors.push(error);
+                    return Type::Error;
+                }
+
+                match op {
+                    AstOp::Not => Type::Bool,
+                    _ => operand_type,
+                }
+            }
+            Expr::Assignment { span: _, lhs, rhs } => {
+                let value_type = self.resolve_expr(&**rhs);
+
+                let (target, name) = match &**lhs {
+                    Expr::Symbol { span, name } => (*span, name),
+                    | Expr::Index { span: _, target: _, index: _ }
+                    | Expr::Field { span: _, target: _, name: _ } => {
+                        return self.resolve_member_assignment(&**lhs, &**rhs, value_type);
+                    }
+                    _ => {
+                        let span = lhs.span();
+                        self.errors.push(
+                            CompilerError::new(
+                                errors::Kind::SyntaxError,
+                                errors::Flag::Abort,
+                                span.start,
+                                span.stop - span.start,
+                                "only a symbol, array element or field can be reassigned with '->'"
+                            )
+                        );
+                        return Type::Error;
+                    }
+                };
+
+                let (mutable, typ, declared) = match self.lookup_mut(name) {
+                    Some(symbol) => {
+                        symbol.reassigned = true;
+                        (symbol.mutable, symbol.typ.clone(), symbol.span)
+                    }
+                    None => {
+                        // Reports the unknown symbol
+                        return self.resolve_symbol(&target, name);
+                    }
+                };
+
+                if !mutable {
+                    self.errors.push(
+                        CompilerError::new(
+                            errors::Kind::NameError,
+                            errors::Flag::Abort,
+                            target.start,
+                            target.stop - target.start,
+                            format!("cannot reassign '{}' because it is not mutable", name).as_str()
+                        ).with_label(
+                            declared.start,
+                            declared.stop - declared.start,
+                            format!("'{}' was declared here, try 'mut {}' to make it mutable", name, name).as_str()
+                        )
+                    );
+                }
+
+                let result = self.unify(&typ, &value_type, rhs.span(), |expected, found| {
+                    format!(
+                        "cannot reassign '{}' of type '{}' with a value of type '{}'",
+                        name,
+                        expected,
+                        found
+                    )
+                });
+                if let Err(error) = result {
+                    let typ = self.substitution.resolve(&typ);
+                    self.errors.push(
+                        error.with_label(
+                            declared.start,
+                            declared.stop - declared.start,
+                            format!("'{}' was declared as a '{}' here", name, typ).as_str()
+                        )
+                    );
+                }
+
+                return typ;
+            }
+            Expr::FunctionCall { span, callee, arguments } => {
+                let callee_type = match &**callee {
+                    Expr::Symbol { span, name } => self.resolve_symbol(span, name),
+                    _ => self.resolve_expr(&**callee),
+                };
+                let mut argument_types: Vec<Type> = vec![];
+                for argument in arguments {
+                    argument_types.push(self.resolve_expr(&**argument));
+                }
+
+                match self.substitution.shallow(&callee_type) {
+                    Type::Error => Type::Error,
+                    Type::Function(parameters, returns) => {
+                        if parameters.len() != arguments.len() {
+                            self.errors.push(
+                                CompilerError::new(
+                                    errors::Kind::TypeError,
+                                    errors::Flag::Abort,
+                                    span.start,
+                                    span.stop - span.start,
+                                    format!(
+                                        "this function takes {} argument(s) but {} were given",
+                                        parameters.len(),
+                                        arguments.len()
+                                    ).as_str()
+                                )
+                            );
+                            return *returns;
+                        }
+
+                        for (i, argument) in arguments.iter().enumerate() {
+                            let result = self.unify(
+                                &parameters[i],
+                                &argument_types[i],
+                                argument.span(),
+                                |expected, found| {
+                                    format!(
+                                        "expected an argument of type '{}' but found '{}'",
+                                        expected,
+                                        found
+                                    )
+                                }
+                            );
+                            if let Err(error) = result {
+                                self.errors.push(error);
+                            }
+                        }
+                        return *returns;
+                    }
+                    Type::Var(_) => {
+                        // Calling something that hasn't been inferred yet, like a parameter,
+                        // tells us that it must be a function
+                        let returns = self.substitution.fresh();
+                        let function = Type::Function(argument_types, Box::new(returns.clone()));
+                        let result = self.unify(&callee_type, &function, *span, |expected, found| {
+                            format!("expected a '{}' but this is called like a '{}'", expected, found)
+                        });
+                        if let Err(error) = result {
+                            self.errors.push(error);
+                        }
+                        return returns;
+                    }
+                    typ => {
+                        let span = callee.span();
+                        self.errors.push(
+                            CompilerError::new(
+                                errors::Kind::TypeError,
+                                errors::Flag::Abort,
+                                span.start,
+                                span.stop - span.start,
+                                format!("this is a '{}', not a function", typ).as_str()
+                            )
+                        );
+                        return Type::Error;
+                    }
+                }
+            }
+            Expr::Array { span: _, elements } => {
+                let element = self.substitution.fresh();
+                for value in elements {
+                    let typ = self.resolve_expr(&**value);
+                    let result = self.unify(&element, &typ, value.span(), |expected, found| {
+                        format!(
+                            "expected every element of this array to be a '{}' but found '{}'",
+                            expected,
+                            found
+                        )
+                    });
+                    if let Err(error) = result {
+                        self.errors.push(error);
+                    }
+                }
+                return Type::Array(Box::new(element));
+            }
+            Expr::Index { span: _, target, index } => {
+                let target_type = self.resolve_expr(&**target);
+                let index_type = self.resolve_expr(&**index);
+
+                let element = self.substitution.fresh();
+                let array = Type::Array(Box::new(element.clone()));
+                let result = self.unify(&array, &target_type, target.span(), |_, found| {
+                    format!("only arrays can be indexed, but this is a '{}'", found)
+                });
+                if let Err(error) = result {
+                    self.errors.push(error);
+                }
+
+                let result = self.unify(&Type::Integer, &index_type, index.span(), |_, found| {
+                    format!("an array index must be an 'int', but this is a '{}'", found)
+                });
+                if let Err(error) = result {
+                    self.errors.push(error);
+                }
+                return element;
+            }
+            Expr::Struct { span, name, fields } => {
+                let layout = self.find_struct(name).map(|def| def.fields.clone());
+                if layout.is_none() {
+                    self.errors.push(
+                        CompilerError::new(
+                            errors::Kind::NameError,
+                            errors::Flag::Abort,
+                            span.start,
+                            span.stop - span.start,
+                            format!("unknown struct '{}'", name).as_str()
+                        )
+                    );
+                }
+
+                let mut given: Vec<&String> = vec![];
+                for field in fields {
+                    let typ = self.resolve_expr(&*field.value);
+                    let layout = match &layout {
+                        Some(layout) => layout,
+                        None => {
+                            continue;
+                        }
+                    };
+
+                    let offset = match layout.iter().position(|(other, _)| *other == &field.name) {
+                        Some(offset) => offset,
+                        None => {
+                            self.errors.push(
+                                CompilerError::new(
+                                    errors::Kind::NameError,
+                                    errors::Flag::Abort,
+                                    field.span.start,
+                                    field.span.stop - field.span.start,
+                                    format!(
+                                        "'{}' has no field named '{}'",
+                                        name,
+                                        field.name
+                                    ).as_str()
+                                )
+                            );
+                            continue;
+                        }
+                    };
+
+                    if given.contains(&&field.name) {
+                        self.errors.push(
+                            CompilerError::new(
+                                errors::Kind::NameError,
+                                errors::Flag::Abort,
+                                field.span.start,
+                                field.span.stop - field.span.start,
+                                format!(
+                                    "the field '{}' is given more than once",
+                                    field.name
+                                ).as_str()
+                            )
+                        );
+                    }
+                    given.push(&field.name);
+                    self.offsets.insert(field.span, offset);
+
+                    let expected = &layout[offset].1;
+                    let result = self.unify(expected, &typ, field.value.span(), |expected, found| {
+                        format!(
+                            "the field '{}' of '{}' is a '{}' but this is a '{}'",
+                            field.name,
+                            name,
+                            expected,
+                            found
+                        )
+                    });
+                    if let Err(error) = result {
+                        self.errors.push(error);
+                    }
+                }
+
+                let layout = match layout {
+                    Some(layout) => layout,
+                    None => {
+                        return Type::Error;
+                    }
+                };
+                let missing: Vec<String> = layout
+                    .iter()
+                    .filter(|(field, _)| !given.contains(field))
+                    .map(|(field, _)| format!("'{}'", field))
+                    .collect();
+                if !missing.is_empty() {
                     self.errors.push(
                         CompilerError::new(
                             errors::Kind::TypeError,
                             errors::Flag::Abort,
-                            span.line,
                             span.start,
                             span.stop - span.start,
-                            "these types are not compatible in a binary expression"
+                            format!(
+                                "this '{}' is missing the field(s) {}",
+                                name,
+                                missing.join(", ")
+                            ).as_str()
                         )
                     );
-                    return Type::None;
                 }
+                return Type::Struct(name.clone());
+            }
+            Expr::Field { span, target, name } => {
+                let target_type = self.resolve_expr(&**target);
+                let struct_name = match self.substitution.shallow(&target_type) {
+                    Type::Error => {
+                        return Type::Error;
+                    }
+                    Type::Struct(struct_name) => struct_name,
+                    Type::Var(_) => {
+                        // Nothing is known about the value yet, which is only enough if a
+                        // single struct in scope has a field of this name
+                        let candidates: Vec<String> = self
+                            .structs()
+                            .filter(|def| def.fields.iter().any(|(field, _)| *field == name))
+                            .map(|def| def.name.clone())
+                            .collect();
+                        let struct_name = match candidates.as_slice() {
+                            [struct_name] => struct_name.clone(),
+                            [] => {
+                                self.errors.push(
+                                    CompilerError::new(
+                                        errors::Kind::TypeError,
+                                        errors::Flag::Abort,
+                                        span.start,
+                                        span.stop - span.start,
+                                        format!("no struct has a field named '{}'", name).as_str()
+                                    )
+                                );
+                                return Type::Error;
+                            }
+                            _ => {
+                                let names: Vec<String> = candidates
+                                    .iter()
+                                    .rev()
+                                    .map(|candidate| format!("'{}'", candidate))
+                                    .collect();
+                                let span = target.span();
+                                self.errors.push(
+                                    CompilerError::new(
+                                        errors::Kind::TypeError,
+                                        errors::Flag::Abort,
+                                        span.start,
+                                        span.stop - span.start,
+                                        format!(
+                                            "this is ambiguous, {} each have a field named '{}'",
+                                            names.join(" and "),
+                                            name
+                                        ).as_str()
+                                    )
+                                );
+                                return Type::Error;
+                            }
+                        };
+
+                        let typ = Type::Struct(struct_name.clone());
+                        let span = target.span();
+                        let result = self.unify(&typ, &target_type, span, |expected, found| {
+                            format!("expected a '{}' but found '{}'", expected, found)
+                        });
+                        if let Err(error) = result {
+                            self.errors.push(error);
+                        }
+                        struct_name
+                    }
+                    typ => {
+                        let span = target.span();
+                        self.errors.push(
+                            CompilerError::new(
+                                errors::Kind::TypeError,
+                                errors::Flag::Abort,
+                                span.start,
+                                span.stop - span.start,
+                                format!("this is a '{}', which has no fields", typ).as_str()
+                            )
+                        );
+                        return Type::Error;
+                    }
+                };
+
+                let field = self
+                    .find_struct(&struct_name)
+                    .and_then(|def| {
+                        def.fields
+                            .iter()
+                            .enumerate()
+                            .find(|(_, (field, _))| *field == name)
+                            .map(|(offset, (_, typ))| (offset, typ.clone()))
+                    });
+                match field {
+                    Some((offset, typ)) => {
+                        self.offsets.insert(*span, offset);
+                        return typ;
+                    }
+                    None => {
+                        self.errors.push(
+                            CompilerError::new(
+                                errors::Kind::TypeError,
+                                errors::Flag::Abort,
+                                span.start,
+                                span.stop - span.start,
+                                format!("'{}' has no field named '{}'", struct_name, name).as_str()
+                            )
+                        );
+                        return Type::Error;
+                    }
+                }
+            }
+            Expr::Match { span, scrutinee, arms } => {
+                let scrutinee_type = self.resolve_expr(&**scrutinee);
+                let result = self.substitution.fresh();
+
+                // Only the arms whose patterns have no errors are checked for exhaustiveness
+                let mut rows: Vec<(Span, Vec<Pat>)> = vec![];
+                let mut valid = true;
+                for arm in arms {
+                    // The names a pattern binds are only in scope for its own arm
+                    self.scopes.push(Context::new());
+                    match self.resolve_pattern(&arm.pattern, &scrutinee_type) {
+                        Some(pat) => rows.push((arm.pattern.span(), vec![pat])),
+                        None => {
+                            valid = false;
+                        }
+                    }
+
+                    let typ = self.resolve_expr(&*arm.body);
+                    let span = arm.body.span();
+                    let same = self.unify(&result, &typ, span, |expected, found| {
+                        format!(
+                            "this arm is a '{}' but the arms before it are '{}'",
+                            found,
+                            expected
+                        )
+                    });
+                    if let Err(error) = same {
+                        self.errors.push(error);
+                    }
+                    self.exit_scope();
+                }
+
+                self.check_match(*span, &rows, valid, &scrutinee_type);
+                return result;
             }
-            _ => unimplemented!(),
+            // The parser has already reported whatever went wrong here
+            Expr::Empty { span: _ } => Type::Error,
+        }
+    }
+
+    /// Checks a pattern against the type of the value it matches, declaring any names
+    /// it binds in the innermost scope. Returns `None` if the pattern has an error, which
+    /// has been reported
+    fn resolve_pattern(&mut self, pattern: &'a Pattern, expected: &Type) -> Option<Pat> {
+        match pattern {
+            // The parser has already reported whatever went wrong here
+            Pattern::Empty { span: _ } => None,
+            Pattern::Wildcard { span: _ } => Some(Pat::Wild),
+            Pattern::Symbol { span, name } => {
+                if self.find_variant(name).is_some() {
+                    return self.resolve_variant_pattern(*span, name, &[], expected);
+                }
+                self.declare(name, false, expected.clone(), *span);
+                return Some(Pat::Wild);
+            }
+            Pattern::Variant { span, name, fields } => {
+                self.resolve_variant_pattern(*span, name, fields, expected)
+            }
+            Pattern::Integer { span, value } => {
+                match self.unify_pattern(&Type::Integer, expected, *span) {
+                    true => Some(Pat::Constructor(Constructor::Integer(*value), vec![])),
+                    false => None,
+                }
+            }
+            Pattern::String { span, value } => {
+                match self.unify_pattern(&Type::String, expected, *span) {
+                    true => Some(Pat::Constructor(Constructor::String(value.clone()), vec![])),
+                    false => None,
+                }
+            }
+            Pattern::Bool { span, value } => {
+                match self.unify_pattern(&Type::Bool, expected, *span) {
+                    true => Some(Pat::Constructor(Constructor::Bool(*value), vec![])),
+                    false => None,
+                }
+            }
+        }
+    }
+
+    fn resolve_variant_pattern(
+        &mut self,
+        span: Span,
+        name: &'a String,
+        fields: &'a [Pattern],
+        expected: &Type
+    ) -> Option<Pat> {
+        let variant = self
+            .find_variant(name)
+            .map(|(def, tag)| (def.name.clone(), tag, def.variants[tag].payload.clone()));
+
+        let (tag, payload, mut valid) = match variant {
+            Some((enum_name, tag, payload)) => {
+                self.tags.insert(span, tag);
+                let valid = self.unify_pattern(&Type::Enum(enum_name), expected, span);
+                (tag, payload, valid)
+            }
+            None => {
+                self.errors.push(
+                    CompilerError::new(
+                        errors::Kind::NameError,
+                        errors::Flag::Abort,
+                        span.start,
+                        span.stop - span.start,
+                        format!("unknown variant '{}'", name).as_str()
+                    )
+                );
+                (0, vec![], false)
+            }
+        };
+
+        if valid && payload.len() != fields.len() {
+            self.errors.push(
+                CompilerError::new(
+                    errors::Kind::TypeError,
+                    errors::Flag::Abort,
+                    span.start,
+                    span.stop - span.start,
+                    format!(
+                        "'{}' holds {} value(s) but this pattern has {}",
+                        name,
+                        payload.len(),
+                        fields.len()
+                    ).as_str()
+                )
+            );
+            valid = false;
+        }
+
+        // Still resolve every field so that the names they bind are declared
+        let mut pats: Vec<Option<Pat>> = vec![];
+        for (i, field) in fields.iter().enumerate() {
+            let typ = match valid {
+                true => payload[i].clone(),
+                false => Type::Error,
+            };
+            pats.push(self.resolve_pattern(field, &typ));
+        }
+
+        if !valid {
+            return None;
+        }
+        let pats = pats.into_iter().collect::<Option<Vec<Pat>>>()?;
+        return Some(Pat::Constructor(Constructor::Variant { tag, name: name.clone() }, pats));
+    }
+
+    /// Requires that a pattern of type `typ` can match the value being matched
+    fn unify_pattern(&mut self, typ: &Type, expected: &Type, span: Span) -> bool {
+        let result = self.unify(expected, typ, span, |expected, found| {
+            format!(
+                "this pattern matches a '{}' but the value being matched is a '{}'",
+                found,
+                expected
+            )
+        });
+        match result {
+            Ok(()) => true,
+            Err(error) => {
+                self.errors.push(error);
+                false
+            }
+        }
+    }
+
+    /// Lists every constructor of a type and the types of their fields, or returns
+    /// `None` if there are too many to list
+    fn signature(&self, typ: &Type) -> Option<Vec<(Constructor, Vec<Type>)>> {
+        match typ {
+            Type::Bool =>
+                Some(
+                    vec![(Constructor::Bool(true), vec![]), (Constructor::Bool(false), vec![])]
+                ),
+            Type::Enum(name) => {
+                let def = self.find_enum(name)?;
+                let signature = def.variants
+                    .iter()
+                    .enumerate()
+                    .map(|(tag, variant)| {
+                        let constructor = Constructor::Variant { tag, name: variant.name.clone() };
+                        (constructor, variant.payload.clone())
+                    })
+                    .collect();
+                return Some(signature);
+            }
+            _ => None,
+        }
+    }
+
+    /// Warns about arms that can never be reached and reports an error if some value
+    /// isn't matched by any arm. `rows` holds the pattern of each arm without errors, and
+    /// whether a value is missed is only checked if every arm is among them
+    fn check_match(
+        &mut self,
+        span: Span,
+        rows: &Vec<(Span, Vec<Pat>)>,
+        complete: bool,
+        scrutinee_type: &Type
+    ) {
+        let types = vec![self.substitution.resolve(scrutinee_type)];
+        if types[0] == Type::Error {
+            return;
+        }
+        let signature = |typ: &Type| self.signature(typ);
+        let checker = Checker::new(&signature);
+
+        let pats: Vec<Vec<Pat>> = rows
+            .iter()
+            .map(|(_, pats)| pats.clone())
+            .collect();
+        let unreachable: Vec<Span> = rows
+            .iter()
+            .enumerate()
+            .filter(|(i, (_, row))| !checker.is_useful(&pats[..*i].to_vec(), row, &types))
+            .map(|(_, (span, _))| *span)
+            .collect();
+        let missing = match complete {
+            true => checker.missing(&pats, &types),
+            false => None,
+        };
+
+        for span in unreachable {
+            self.errors.push(
+                CompilerError::new(
+                    errors::Kind::UnreachablePattern,
+                    errors::Flag::Warning,
+                    span.start,
+                    span.stop - span.start,
+                    "this arm can never be reached, the arms before it match everything it does"
+                )
+            );
+        }
+
+        if let Some(witness) = missing {
+            self.errors.push(
+                CompilerError::new(
+                    errors::Kind::NonExhaustive,
+                    errors::Flag::Abort,
+                    span.start,
+                    span.stop - span.start,
+                    format!(
+                        "this match doesn't handle every case, '{}' is not matched by any arm",
+                        witness[0]
+                    ).as_str()
+                )
+            );
         }
     }
 
     pub fn resolve_names(&mut self, ast: &'a Vec<Stmt>) {
+        self.scopes.last_mut().unwrap().predeclare(ast);
+
+        // Top level types can be used anywhere, even before they are declared
+        for stmt in ast {
+            self.declare_type(stmt);
+        }
+        for stmt in ast {
+            self.define_type(stmt);
+        }
+        self.hoist(ast);
+
         for stmt in ast {
             self.resolve_stmt(stmt);
         }
+
+        let mut symbols: Vec<&Symbol<'a>> = self.scopes[0].symbols.values().collect();
+        symbols.sort_by_key(|symbol| symbol.span.start);
+        let warnings: Vec<CompilerError> = symbols
+            .iter()
+            .flat_map(|symbol| symbol.check_usage())
+            .collect();
+        self.errors.extend(warnings);
+    }
+
+    /// Resolves the statements of a block in a scope of their own
+    fn resolve_block(&mut self, body: &'a Vec<Stmt>) {
+        self.enter_scope(body);
+        for stmt in body {
+            self.resolve_stmt(stmt);
+        }
+        self.exit_scope();
+    }
+
+    /// Checks that the condition of an `if` or `while` is a boolean
+    fn resolve_condition(&mut self, condition: &'a Expr, keyword: &str) {
+        let typ = self.resolve_expr(condition);
+        let result = self.unify(&Type::Bool, &typ, condition.span(), |_, found| {
+            format!("the condition of a '{}' must be a 'bool', but this is a '{}'", keyword, found)
+        });
+        if let Err(error) = result {
+            self.errors.push(error);
+        }
     }
 
     fn resolve_stmt(&mut self, stmt: &'a Stmt) {
         match stmt {
-            Stmt::Binding { span, mutable, name, initializer, annotation: _ } => {
-                let value = initializer.as_ref().unwrap_or_else(|| { unimplemented!() });
-                let typ = self.resolve_expr(&*value);
-                self.global[self.current_ctx].load(name, *mutable, typ);
+            Stmt::Binding { span, mutable, name, name_span, initializer, annotation } => {
+                self.initializing = Some(*span);
+                let (mut typ, value_span) = match initializer {
+                    Some(value) => (self.resolve_expr(&**value), value.span()),
+                    // Nothing to infer from yet, so leave it to however the binding is used
+                    None => (self.substitution.fresh(), *span),
+                };
+                self.initializing = None;
+
+                if let Some(annotation) = annotation {
+                    let expected = self.resolve_type(annotation);
+                    let result = self.unify(&expected, &typ, value_span, |expected, found| {
+                        format!("expected a value of type '{}' but found '{}'", expected, found)
+                    });
+                    if let Err(error) = result {
+                        let annotation_span = annotation.span();
+                        self.errors.push(
+                            error.with_label(
+                                annotation_span.start,
+                                annotation_span.stop - annotation_span.start,
+                                "expected because of this annotation"
+                            )
+                        );
+                    }
+
+                    // The annotation is what the rest of the program relies on
+                    typ = expected;
+                }
+
+                self.declare(name, *mutable, typ, *name_span);
             }
-            _ => unimplemented!(),
+            Stmt::Function { span: _, name, name_span, parameters, body } => {
+                // The function was declared when its scope was entered, unless another
+                // declaration already took its name, which has been reported
+                let hoisted = self.scopes
+                    .last()
+                    .and_then(|ctx| ctx.symbols.get(name))
+                    .filter(|symbol| symbol.span == *name_span)
+                    .map(|symbol| symbol.typ.clone());
+                let (parameter_types, returns) = match hoisted {
+                    Some(Type::Function(parameter_types, returns)) => (parameter_types, *returns),
+                    _ => {
+                        let parameter_types: Vec<Type> = parameters
+                            .iter()
+                            .map(|_| self.substitution.fresh())
+                            .collect();
+                        (parameter_types, self.substitution.fresh())
+                    }
+                };
+                let typ = Type::Function(parameter_types.clone(), Box::new(returns.clone()));
+
+                // Parameters live in a fresh scope that is discarded with the body,
+                // and loops around the declaration can't be broken out of from inside it
+                self.enter_scope(body);
+                self.returns.push(returns);
+                let loops = std::mem::replace(&mut self.loops, 0);
+
+                for (parameter, typ) in parameters.iter().zip(parameter_types) {
+                    self.declare(&parameter.name, false, typ, parameter.span);
+                }
+                for stmt in body {
+                    self.resolve_stmt(stmt);
+                }
+
+                self.loops = loops;
+                let returns = self.returns.pop().unwrap();
+                self.exit_scope();
+
+                // Reaching the end of the body returns none, which has to agree with
+                // whatever the function returns elsewhere
+                if !Self::always_returns(body) {
+                    let result = self.unify(&returns, &Type::Unit, *name_span, |expected, _| {
+                        format!("not all paths of this function return its '{}'", expected)
+                    });
+                    if let Err(error) = result {
+                        self.errors.push(error);
+                    }
+                }
+                self.generalize(name, &typ);
+            }
+            Stmt::If { span: _, condition, body, else_branch } => {
+                self.resolve_condition(condition, "if");
+                self.resolve_block(body);
+
+                if let Some(else_branch) = else_branch {
+                    self.resolve_stmt(else_branch);
+                }
+            }
+            Stmt::While { span: _, condition, body } => {
+                self.resolve_condition(condition, "while");
+
+                self.loops += 1;
+                self.resolve_block(body);
+                self.loops -= 1;
+            }
+            Stmt::Break { span } | Stmt::Continue { span } => {
+                if self.loops == 0 {
+                    let keyword = match stmt {
+                        Stmt::Break { span: _ } => "break",
+                        _ => "continue",
+                    };
+                    self.errors.push(
+                        CompilerError::new(
+                            errors::Kind::SyntaxError,
+                            errors::Flag::Abort,
+                            span.start,
+                            span.stop - span.start,
+                            format!("'{}' can only be used inside of a loop", keyword).as_str()
+                        )
+                    );
+                }
+            }
+            Stmt::Block { span: _, body } => self.resolve_block(body),
+            | Stmt::Struct { span: _, name: _, fields: _ }
+            | Stmt::Enum { span: _, name: _, variants: _ } => {
+                // Top level types have already been resolved by `resolve_names`
+                if self.scopes.len() > 1 {
+                    self.declare_type(stmt);
+                    self.define_type(stmt);
+                }
+            }
+            Stmt::Return { span, value } => {
+                let returns = match self.returns.last() {
+                    Some(returns) => Some(returns.clone()),
+                    None => {
+                        self.errors.push(
+                            CompilerError::new(
+                                errors::Kind::SyntaxError,
+                                errors::Flag::Abort,
+                                span.start,
+                                span.stop - span.start,
+                                "'return' can only be used inside of a function"
+                            )
+                        );
+                        None
+                    }
+                };
+
+                let (typ, value_span) = match value {
+                    Some(value) => (self.resolve_expr(&**value), value.span()),
+                    None => (Type::Unit, *span),
+                };
+
+                if let Some(returns) = returns {
+                    let result = self.unify(&returns, &typ, value_span, |expected, found| {
+                        format!(
+                            "this function returns '{}' but this value is a '{}'",
+                            expected,
+                            found
+                        )
+                    });
+                    if let Err(error) = result {
+                        self.errors.push(error);
+                    }
+                }
+            }
+            Stmt::Expression { span: _, expr } => {
+                self.resolve_expr(expr);
+            }
+            Stmt::Empty { span: _ } => {}
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::tests::parse;
+
+    /// Resolves `source`, returning the message and labels of each error that stops it from
+    /// being compiled
+    fn errors(source: &str) -> Vec<(String, Vec<String>)> {
+        let tree = parse(source);
+        let mut resolver = Resolver::new();
+        resolver.resolve_names(&tree);
+        return resolver.errors
+            .iter()
+            .filter(|error| matches!(error.flag, errors::Flag::Abort))
+            .map(|error| {
+                let labels = error.labels().iter().map(|label| label.to_string()).collect();
+                (error.message().to_string(), labels)
+            })
+            .collect();
+    }
+
+    /// Resolves `source`, returning how many arms it warns can never be reached
+    fn unreachable_arms(source: &str) -> usize {
+        let tree = parse(source);
+        let mut resolver = Resolver::new();
+        resolver.resolve_names(&tree);
+        return resolver.errors
+            .iter()
+            .filter(|error| matches!(error.kind, errors::Kind::UnreachablePattern))
+            .count();
+    }
+
+    #[test]
+    fn calls_cite_where_a_parameter_was_inferred() {
+        let source = "function f(x) {\n    return x * 2.0\n}\ny = f(\"s\")\n";
+        assert_eq!(
+            errors(source),
+            [
+                (
+                    "expected an argument of type 'float' but found 'str'".to_string(),
+                    vec!["'float' was inferred from this".to_string()],
+                ),
+            ]
+        );
+
+        // Each call of a generic function infers its own types
+        let source = "function id(x) {\n    return x\n}\na = id(1) + 2\nb = id(\"s\")\n";
+        assert_eq!(errors(source), []);
+    }
+
+    #[test]
+    fn all_paths_return_a_value() {
+        let source = "function f(x) {\n    if x {\n        return 1\n    }\n}\n";
+        assert_eq!(
+            errors(source),
+            [
+                (
+                    "not all paths of this function return its 'int'".to_string(),
+                    vec!["'int' was inferred from this".to_string()],
+                ),
+            ]
+        );
+
+        let source =
+            "function f(x) {\n    if x {\n        return 1\n    } else {\n        return 2\n    }\n}\n";
+        assert_eq!(errors(source), []);
+        assert_eq!(errors("function f(x) {\n    if x {\n        return\n    }\n}\n"), []);
+        assert_eq!(errors("function f() {\n    {\n        return 1\n    }\n}\n"), []);
+    }
+
+    #[test]
+    fn constructors_with_payloads_are_only_called() {
+        let source = "enum Shape {\n    Circle(float)\n    Empty\n}\n";
+        assert_eq!(
+            errors(&format!("{}g = Circle\nc = g(1.0)\n", source)),
+            [("'Circle' has a payload, so it can only be called".to_string(), vec![])]
+        );
+        assert_eq!(errors(&format!("{}c = Circle(1.0)\ne = Empty\n", source)), []);
+    }
+
+    #[test]
+    fn matches_with_errors() {
+        // Nothing can be said about the cases of a value whose type has an error
+        let source = "m = match nope {\n    1 => 2\n}\n";
+        assert_eq!(errors(source), [("unknown symbol 'nope'".to_string(), vec![])]);
+
+        // An arm with an error neither hides the arms after it nor leaves cases unmatched
+        let source =
+            "enum Shape {\n    Circle(float)\n    Empty\n}\ns = Empty\nm = match s {
+    Circl(r) => 1
+    Circle(\"r\") => 2
+    Circle(r) => 3
+    Empty => 4
+}
+";
+        let messages: Vec<String> = errors(source)
+            .into_iter()
+            .map(|(message, _)| message)
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                "unknown variant 'Circl'",
+                "this pattern matches a 'str' but the value being matched is a 'float'",
+            ]
+        );
+        assert_eq!(unreachable_arms(source), 0);
+    }
+}
@@ -0,0 +1,368 @@
+use std::fmt::Display;
+
+use crate::ast::{ AstOp, Span };
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum Type {
+    /// What a function that doesn't return a value returns
+    Unit,
+    /// The type of something that has already been reported as an error. It unifies with
+    /// anything, so that one mistake isn't reported again everywhere it is used
+    Error,
+    Integer,
+    Float,
+    String,
+    Bool,
+    Array(Box<Type>),
+    /// A user defined struct, which is only ever equal to itself
+    Struct(String),
+    /// A user defined enum, which is only ever equal to itself
+    Enum(String),
+
+    /// A type that has not been inferred yet, see `Substitution`
+    Var(usize),
+    Function(Vec<Type>, Box<Type>),
+}
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Unit => write!(f, "none"),
+            Type::Error => write!(f, "error"),
+            Type::Integer => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::String => write!(f, "str"),
+            Type::Bool => write!(f, "bool"),
+            Type::Array(element) => write!(f, "[{}]", element),
+            Type::Struct(name) | Type::Enum(name) => write!(f, "{}", name),
+            Type::Var(id) => write!(f, "T{}", id),
+            Type::Function(parameters, returns) => {
+                let parameters: Vec<String> = parameters
+                    .iter()
+                    .map(|parameter| parameter.to_string())
+                    .collect();
+                write!(f, "function({}) -> {}", parameters.join(", "), returns)
+            }
+        }
+    }
+}
+
+/// Returns the type produced by applying a binary operator to operands of the given
+/// types, or `None` if the operator is not defined for them. There are no implicit
+/// conversions, so `1 + 2.0` is an error
+pub fn binary_type(op: &AstOp, lhs: &Type, rhs: &Type) -> Option<Type> {
+    if lhs != rhs {
+        return None;
+    }
+
+    match (op, lhs) {
+        (AstOp::Plus, Type::Integer | Type::Float | Type::String) => Some(lhs.clone()),
+        (
+            AstOp::Minus | AstOp::Multiply | AstOp::Divide | AstOp::Modulus,
+            Type::Integer | Type::Float,
+        ) => Some(lhs.clone()),
+        (AstOp::Equal | AstOp::NotEqual, Type::Integer | Type::Float | Type::String | Type::Bool) =>
+            Some(Type::Bool),
+        (
+            AstOp::Less | AstOp::LessEqual | AstOp::Greater | AstOp::GreaterEqual,
+            Type::Integer | Type::Float | Type::String,
+        ) => Some(Type::Bool),
+        _ => None,
+    }
+}
+
+/// Returns the type produced by applying a prefix operator to an operand of the given
+/// type, or `None` if the operator is not defined for it
+pub fn unary_type(op: &AstOp, operand: &Type) -> Option<Type> {
+    match (op, operand) {
+        (AstOp::Negate, Type::Integer | Type::Float) => Some(operand.clone()),
+        (AstOp::Not, Type::Bool) => Some(Type::Bool),
+        _ => None,
+    }
+}
+
+/// Checks whether an operator can be applied to operands of this type
+fn supports(op: &AstOp, typ: &Type) -> bool {
+    match op {
+        AstOp::Negate | AstOp::Not => unary_type(op, typ).is_some(),
+        _ => binary_type(op, typ, typ).is_some(),
+    }
+}
+
+/// Describes why two types could not be unified
+pub enum Mismatch {
+    /// The two types are different
+    Types {
+        expected: Type,
+        found: Type,
+    },
+    /// Binding a type variable would make it contain itself
+    Infinite {
+        var: Type,
+        typ: Type,
+    },
+    /// A type variable was inferred to be a type that doesn't support an operator it was used with
+    Operator {
+        op: AstOp,
+        typ: Type,
+        span: Span,
+    },
+}
+
+/// The state of a single type variable
+struct Variable {
+    binding: Option<Type>,
+    /// Where the binding was inferred from
+    origin: Option<Span>,
+    /// Operators this variable has been used with, and where
+    constraints: Vec<(AstOp, Span)>,
+}
+
+/// Maps type variables to what has been inferred about them so far
+pub struct Substitution {
+    variables: Vec<Variable>,
+}
+
+impl Substitution {
+    pub fn new() -> Self {
+        Substitution { variables: vec![] }
+    }
+
+    /// Creates a new type variable that has not been inferred yet
+    pub fn fresh(&mut self) -> Type {
+        self.variables.push(Variable { binding: None, origin: None, constraints: vec![] });
+        return Type::Var(self.variables.len() - 1);
+    }
+
+    /// Follows a chain of bound variables until reaching a type that isn't one
+    pub fn shallow(&self, typ: &Type) -> Type {
+        let mut typ = typ.clone();
+        while let Type::Var(id) = typ {
+            match &self.variables[id].binding {
+                Some(binding) => {
+                    typ = binding.clone();
+                }
+                None => {
+                    break;
+                }
+            }
+        }
+        return typ;
+    }
+
+    /// Replaces every bound variable in a type with what it is bound to
+    pub fn resolve(&self, typ: &Type) -> Type {
+        match self.shallow(typ) {
+            Type::Array(element) => Type::Array(Box::new(self.resolve(&element))),
+            Type::Function(parameters, returns) =>
+                Type::Function(
+                    parameters
+                        .iter()
+                        .map(|parameter| self.resolve(parameter))
+                        .collect(),
+                    Box::new(self.resolve(&returns))
+                ),
+            typ => typ,
+        }
+    }
+
+    /// Returns where the type behind a chain of variables was first inferred from
+    pub fn origin(&self, typ: &Type) -> Option<Span> {
+        let mut typ = typ.clone();
+        while let Type::Var(id) = typ {
+            let variable = &self.variables[id];
+            if variable.origin.is_some() {
+                return variable.origin;
+            }
+            match &variable.binding {
+                Some(binding) => {
+                    typ = binding.clone();
+                }
+                None => {
+                    break;
+                }
+            }
+        }
+        return None;
+    }
+
+    /// Makes two types equal by binding variables in either of them. `span` is the
+    /// expression that required the types to be equal and is recorded as the origin
+    /// of any variable bound here
+    pub fn unify(&mut self, expected: &Type, found: &Type, span: Span) -> Result<(), Mismatch> {
+        let expected = self.shallow(expected);
+        let found = self.shallow(found);
+
+        match (&expected, &found) {
+            (Type::Var(a), Type::Var(b)) if a == b => Ok(()),
+            (Type::Var(id), _) => self.bind(*id, found, span),
+            (_, Type::Var(id)) => self.bind(*id, expected, span),
+
+            // Something has already gone wrong, don't report it twice
+            (Type::Error, _) | (_, Type::Error) => Ok(()),
+
+            (Type::Array(expected_element), Type::Array(found_element)) => {
+                let mismatch = Mismatch::Types {
+                    expected: self.resolve(&expected),
+                    found: self.resolve(&found),
+                };
+                return self
+                    .unify(expected_element, found_element, span)
+                    .map_err(|inner| Self::widen(inner, mismatch));
+            }
+
+            (
+                Type::Function(expected_parameters, expected_returns),
+                Type::Function(found_parameters, found_returns),
+            ) => {
+                let mismatch = Mismatch::Types {
+                    expected: self.resolve(&expected),
+                    found: self.resolve(&found),
+                };
+                if expected_parameters.len() != found_parameters.len() {
+                    return Err(mismatch);
+                }
+
+                let pairs = expected_parameters.iter().zip(found_parameters.iter());
+                for (expected, found) in pairs {
+                    if let Err(inner) = self.unify(expected, found, span) {
+                        return Err(Self::widen(inner, mismatch));
+                    }
+                }
+                if let Err(inner) = self.unify(expected_returns, found_returns, span) {
+                    return Err(Self::widen(inner, mismatch));
+                }
+                return Ok(());
+            }
+
+            _ if expected == found => Ok(()),
+            _ =>
+                Err(Mismatch::Types {
+                    expected: self.resolve(&expected),
+                    found: self.resolve(&found),
+                }),
+        }
+    }
+
+    /// Reports a mismatch between two parts of a larger type as a mismatch of the
+    /// whole types, which reads better. Other kinds of mismatch are kept as they are
+    fn widen(inner: Mismatch, outer: Mismatch) -> Mismatch {
+        match inner {
+            Mismatch::Types { expected: _, found: _ } => outer,
+            _ => inner,
+        }
+    }
+
+    fn bind(&mut self, id: usize, typ: Type, span: Span) -> Result<(), Mismatch> {
+        if self.occurs(id, &typ) {
+            return Err(Mismatch::Infinite { var: Type::Var(id), typ: self.resolve(&typ) });
+        }
+
+        let variable = &mut self.variables[id];
+        variable.binding = Some(typ.clone());
+        variable.origin = variable.origin.or(Some(span));
+        let constraints = std::mem::take(&mut variable.constraints);
+
+        // Whatever this variable was used with now applies to what it is bound to
+        for (op, op_span) in constraints {
+            self.constrain(&typ, op, op_span)?;
+        }
+        return Ok(());
+    }
+
+    /// Checks whether the variable `id` appears anywhere inside of `typ`
+    fn occurs(&self, id: usize, typ: &Type) -> bool {
+        match self.shallow(typ) {
+            Type::Var(other) => other == id,
+            Type::Array(element) => self.occurs(id, &element),
+            Type::Function(parameters, returns) => {
+                parameters.iter().any(|parameter| self.occurs(id, parameter)) ||
+                    self.occurs(id, &returns)
+            }
+            _ => false,
+        }
+    }
+
+    /// Requires that `op` can be applied to values of `typ`. If `typ` has not been
+    /// inferred yet, the check is deferred until it is
+    pub fn constrain(&mut self, typ: &Type, op: AstOp, span: Span) -> Result<(), Mismatch> {
+        match self.shallow(typ) {
+            Type::Var(id) => {
+                self.variables[id].constraints.push((op, span));
+                return Ok(());
+            }
+            Type::Error => Ok(()),
+            typ if supports(&op, &typ) => Ok(()),
+            typ => Err(Mismatch::Operator { op, typ: self.resolve(&typ), span }),
+        }
+    }
+
+    /// Collects the variables in a type that have not been inferred yet
+    pub fn free_vars(&self, typ: &Type, out: &mut Vec<usize>) {
+        match self.shallow(typ) {
+            Type::Var(id) => {
+                if !out.contains(&id) {
+                    out.push(id);
+                }
+            }
+            Type::Array(element) => self.free_vars(&element, out),
+            Type::Function(parameters, returns) => {
+                for parameter in &parameters {
+                    self.free_vars(parameter, out);
+                }
+                self.free_vars(&returns, out);
+            }
+            _ => {}
+        }
+    }
+
+    /// Replaces each of the `generics` in a type with a fresh variable, carrying over
+    /// any operators the generic is required to support
+    pub fn instantiate(&mut self, generics: &Vec<usize>, typ: &Type) -> Type {
+        if generics.is_empty() {
+            return typ.clone();
+        }
+
+        let mut mapping: Vec<(usize, Type)> = vec![];
+        for generic in generics {
+            let fresh = self.fresh();
+            if let Type::Var(id) = fresh {
+                self.variables[id].constraints = self.variables[*generic].constraints.clone();
+            }
+            mapping.push((*generic, fresh));
+        }
+
+        return self.replace(typ, &mapping);
+    }
+
+    /// Replaces the generics of `mapping` in a type. Bound variables are only followed if
+    /// there is a generic behind them, otherwise they are kept so that what they were
+    /// inferred from can still be found
+    fn replace(&self, typ: &Type, mapping: &Vec<(usize, Type)>) -> Type {
+        match typ {
+            Type::Var(id) => {
+                if let Some((_, fresh)) = mapping.iter().find(|(generic, _)| generic == id) {
+                    return fresh.clone();
+                }
+
+                let mut vars: Vec<usize> = vec![];
+                self.free_vars(typ, &mut vars);
+                match &self.variables[*id].binding {
+                    Some(binding) if vars.iter().any(|var| mapping.iter().any(|(g, _)| g == var)) =>
+                        self.replace(binding, mapping),
+                    _ => typ.clone(),
+                }
+            }
+            Type::Array(element) => Type::Array(Box::new(self.replace(element, mapping))),
+            Type::Function(parameters, returns) =>
+                Type::Function(
+                    parameters
+                        .iter()
+                        .map(|parameter| self.replace(parameter, mapping))
+                        .collect(),
+                    Box::new(self.replace(returns, mapping))
+                ),
+            _ => typ.clone(),
+        }
+    }
+}
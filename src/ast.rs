@@ -2,21 +2,36 @@ use std::fmt::Display;
 
 use crate::token;
 
-#[derive(Debug, Clone, Copy)]
+/// A range of the source in bytes, `stop` being exclusive
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct Span {
-    pub line: usize,
     pub start: usize,
     pub stop: usize,
     pub valid: bool,
 }
 
 impl Span {
-    pub fn new(line: usize, start: usize, stop: usize) -> Self {
-        Self { line, start, stop, valid: true }
+    pub fn new(start: usize, stop: usize) -> Self {
+        Self { start, stop, valid: true }
+    }
+
+    /// Returns a span covering both spans and everything in between
+    pub fn merge(&self, other: &Span) -> Span {
+        Span {
+            start: self.start.min(other.start),
+            stop: self.stop.max(other.stop),
+            valid: self.valid && other.valid,
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Associativity {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AstOp {
     Plus,
     Minus,
@@ -24,6 +39,16 @@ pub enum AstOp {
     Divide,
     Modulus,
     Reassign,
+
+    Equal,
+    NotEqual,
+    Less,
+    LessEqual,
+    Greater,
+    GreaterEqual,
+
+    Negate,
+    Not,
 }
 
 impl AstOp {
@@ -35,6 +60,21 @@ impl AstOp {
             token::Kind::Slash => Some(AstOp::Divide),
             token::Kind::Modulo => Some(AstOp::Modulus),
             token::Kind::Arrow => Some(AstOp::Reassign),
+            token::Kind::EqualEqual => Some(AstOp::Equal),
+            token::Kind::BangEqual => Some(AstOp::NotEqual),
+            token::Kind::Less => Some(AstOp::Less),
+            token::Kind::LessEqual => Some(AstOp::LessEqual),
+            token::Kind::More => Some(AstOp::Greater),
+            token::Kind::MoreEqual => Some(AstOp::GreaterEqual),
+            _ => None,
+        }
+    }
+
+    /// Returns the prefix operator for a token, which may differ from its binary meaning
+    pub fn unary_from_token(token: &token::Kind) -> Option<Self> {
+        match token {
+            token::Kind::Minus => Some(AstOp::Negate),
+            token::Kind::Bang => Some(AstOp::Not),
             _ => None,
         }
     }
@@ -42,11 +82,41 @@ impl AstOp {
     pub fn precedence(&self) -> i8 {
         match self {
             AstOp::Reassign => -1,
-            AstOp::Plus => 0,
-            AstOp::Minus => 0,
-            AstOp::Multiply => 1,
-            AstOp::Divide => 1,
-            AstOp::Modulus => 1,
+            AstOp::Equal => 0,
+            AstOp::NotEqual => 0,
+            AstOp::Less => 0,
+            AstOp::LessEqual => 0,
+            AstOp::Greater => 0,
+            AstOp::GreaterEqual => 0,
+            AstOp::Plus => 1,
+            AstOp::Minus => 1,
+            AstOp::Multiply => 2,
+            AstOp::Divide => 2,
+            AstOp::Modulus => 2,
+            AstOp::Negate => 3,
+            AstOp::Not => 3,
+        }
+    }
+
+    /// Determines how a chain of operators with the same precedence groups, e.g.
+    /// `10 - 3 - 2` is `(10 - 3) - 2` while `a -> b -> c` is `a -> (b -> c)`
+    pub fn associativity(&self) -> Associativity {
+        match self {
+            AstOp::Reassign => Associativity::Right,
+            _ => Associativity::Left,
+        }
+    }
+
+    /// Returns true if this operator compares its operands and produces a boolean
+    pub fn is_comparison(&self) -> bool {
+        match self {
+            | AstOp::Equal
+            | AstOp::NotEqual
+            | AstOp::Less
+            | AstOp::LessEqual
+            | AstOp::Greater
+            | AstOp::GreaterEqual => true,
+            _ => false,
         }
     }
 }
@@ -60,6 +130,14 @@ impl Display for AstOp {
             AstOp::Divide => write!(f, "DIVIDE '/'"),
             AstOp::Modulus => write!(f, "MODULUS '%'"),
             AstOp::Reassign => write!(f, "REASSIGN '->'"),
+            AstOp::Equal => write!(f, "EQUAL '=='"),
+            AstOp::NotEqual => write!(f, "NOT_EQUAL '!='"),
+            AstOp::Less => write!(f, "LESS '<'"),
+            AstOp::LessEqual => write!(f, "LESS_EQUAL '<='"),
+            AstOp::Greater => write!(f, "GREATER '>'"),
+            AstOp::GreaterEqual => write!(f, "GREATER_EQUAL '>='"),
+            AstOp::Negate => write!(f, "NEGATE '-'"),
+            AstOp::Not => write!(f, "NOT '!'"),
         }
     }
 }
@@ -83,6 +161,10 @@ pub enum Expr {
         span: Span,
         value: String,
     },
+    Bool {
+        span: Span,
+        value: bool,
+    },
     Symbol {
         span: Span,
         name: String,
@@ -114,20 +196,181 @@ pub enum Expr {
         callee: Box<Expr>,
         arguments: Vec<Box<Expr>>,
     },
+    Array {
+        span: Span,
+        elements: Vec<Box<Expr>>,
+    },
+    Index {
+        span: Span,
+        target: Box<Expr>,
+        index: Box<Expr>,
+    },
+    /// A struct literal, e.g. `Point { x: 1, y: 2 }`
+    Struct {
+        span: Span,
+        name: String,
+        fields: Vec<FieldInit>,
+    },
+    /// A field access, e.g. `p.x`
+    Field {
+        span: Span,
+        target: Box<Expr>,
+        name: String,
+    },
+    Match {
+        span: Span,
+        scrutinee: Box<Expr>,
+        arms: Vec<MatchArm>,
+    },
 }
 
 impl Expr {
+    pub fn span(&self) -> Span {
+        match self {
+            | Expr::Empty { span }
+            | Expr::Float { span, value: _ }
+            | Expr::Integer { span, value: _ }
+            | Expr::String { span, value: _ }
+            | Expr::Bool { span, value: _ }
+            | Expr::Symbol { span, name: _ }
+            | Expr::Grouping { span, inner: _ }
+            | Expr::Binary { span, lhs: _, rhs: _, op: _ }
+            | Expr::Unary { span, op: _, operand: _ }
+            | Expr::Assignment { span, lhs: _, rhs: _ }
+            | Expr::FunctionCall { span, callee: _, arguments: _ }
+            | Expr::Array { span, elements: _ }
+            | Expr::Index { span, target: _, index: _ }
+            | Expr::Struct { span, name: _, fields: _ }
+            | Expr::Field { span, target: _, name: _ }
+            | Expr::Match { span, scrutinee: _, arms: _ } => *span,
+        }
+    }
+
     pub fn is_terminal(&self) -> bool {
         match self {
             | Expr::Integer { span: _, value: _ }
             | Expr::Float { span: _, value: _ }
             | Expr::String { span: _, value: _ }
+            | Expr::Bool { span: _, value: _ }
             | Expr::Symbol { span: _, name: _ } => true,
             _ => false,
         }
     }
 }
 
+#[derive(Debug)]
+pub enum Pattern {
+    Empty {
+        span: Span,
+    },
+    Wildcard {
+        span: Span,
+    },
+    /// Either matches a variant without a payload, or binds the value to a new name
+    Symbol {
+        span: Span,
+        name: String,
+    },
+    /// A variant and patterns for each value in its payload, e.g. `Rect(w, _)`
+    Variant {
+        span: Span,
+        name: String,
+        fields: Vec<Pattern>,
+    },
+    Integer {
+        span: Span,
+        value: i32,
+    },
+    String {
+        span: Span,
+        value: String,
+    },
+    Bool {
+        span: Span,
+        value: bool,
+    },
+}
+
+impl Pattern {
+    pub fn span(&self) -> Span {
+        match self {
+            | Pattern::Empty { span }
+            | Pattern::Wildcard { span }
+            | Pattern::Symbol { span, name: _ }
+            | Pattern::Variant { span, name: _, fields: _ }
+            | Pattern::Integer { span, value: _ }
+            | Pattern::String { span, value: _ }
+            | Pattern::Bool { span, value: _ } => *span,
+        }
+    }
+}
+
+/// A single `pattern => value` of a match expression
+#[derive(Debug)]
+pub struct MatchArm {
+    pub pattern: Pattern,
+    pub body: Box<Expr>,
+}
+
+/// The syntax of a type as written in an annotation, e.g. the `int` in `x: int = 5`
+#[derive(Debug)]
+pub enum TypeExpr {
+    Empty {
+        span: Span,
+    },
+    Named {
+        span: Span,
+        name: String,
+    },
+    /// `[T]`, an array of `T`
+    Array {
+        span: Span,
+        element: Box<TypeExpr>,
+    },
+}
+
+impl TypeExpr {
+    pub fn span(&self) -> Span {
+        match self {
+            | TypeExpr::Empty { span }
+            | TypeExpr::Named { span, name: _ }
+            | TypeExpr::Array { span, element: _ } => *span,
+        }
+    }
+}
+
+/// A parameter of a function declaration, whose span is that of its name
+#[derive(Debug)]
+pub struct Parameter {
+    pub span: Span,
+    pub name: String,
+}
+
+/// A field of a struct declaration, e.g. the `x: int` in `struct Point { x: int }`
+#[derive(Debug)]
+pub struct Field {
+    pub span: Span,
+    pub name: String,
+    pub annotation: TypeExpr,
+}
+
+/// A variant of an enum declaration, e.g. the `Rect(float, float)` in
+/// `enum Shape { Rect(float, float) }`
+#[derive(Debug)]
+pub struct Variant {
+    pub span: Span,
+    pub name: String,
+    pub payload: Vec<TypeExpr>,
+}
+
+/// The value given to a field in a struct literal, e.g. the `x: 1` in `Point { x: 1 }`
+#[derive(Debug)]
+pub struct FieldInit {
+    pub span: Span,
+    pub name: String,
+    pub value: Box<Expr>,
+}
+
 #[derive(Debug)]
 pub enum Stmt {
     Empty {
@@ -138,12 +381,350 @@ pub enum Stmt {
         span: Span,
         mutable: bool,
         name: String,
+        /// The span of just the name being bound
+        name_span: Span,
         initializer: Option<Box<Expr>>,
-        annotation: Option<Box<Expr>>,
+        annotation: Option<TypeExpr>,
     },
 
     Expression {
         span: Span,
         expr: Expr,
     },
+    Block {
+        span: Span,
+        body: Vec<Stmt>,
+    },
+
+    Function {
+        span: Span,
+        name: String,
+        /// The span of just the function's name
+        name_span: Span,
+        parameters: Vec<Parameter>,
+        body: Vec<Stmt>,
+    },
+    Return {
+        span: Span,
+        value: Option<Box<Expr>>,
+    },
+    Struct {
+        span: Span,
+        name: String,
+        fields: Vec<Field>,
+    },
+    Enum {
+        span: Span,
+        name: String,
+        variants: Vec<Variant>,
+    },
+    If {
+        span: Span,
+        condition: Box<Expr>,
+        body: Vec<Stmt>,
+        /// Either a `Stmt::Block` for `else { ... }` or a `Stmt::If` for `else if ...`
+        else_branch: Option<Box<Stmt>>,
+    },
+    While {
+        span: Span,
+        condition: Box<Expr>,
+        body: Vec<Stmt>,
+    },
+    Break {
+        span: Span,
+    },
+    Continue {
+        span: Span,
+    },
+}
+
+impl Stmt {
+    pub fn span(&self) -> Span {
+        match self {
+            Stmt::Binding {
+                span,
+                mutable: _,
+                name: _,
+                name_span: _,
+                initializer: _,
+                annotation: _,
+            } => *span,
+            | Stmt::Empty { span }
+            | Stmt::Expression { span, expr: _ }
+            | Stmt::Block { span, body: _ }
+            | Stmt::Function { span, name: _, name_span: _, parameters: _, body: _ }
+            | Stmt::Return { span, value: _ }
+            | Stmt::Struct { span, name: _, fields: _ }
+            | Stmt::Enum { span, name: _, variants: _ }
+            | Stmt::If { span, condition: _, body: _, else_branch: _ }
+            | Stmt::While { span, condition: _, body: _ }
+            | Stmt::Break { span }
+            | Stmt::Continue { span } => *span,
+        }
+    }
+}
+
+/// Walks the AST without modifying it. Each method defaults to visiting the children of
+/// its node, so a pass only overrides the nodes it cares about and calls the matching
+/// `walk_*` function if it still wants to reach their children
+pub trait Visitor: Sized {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        walk_stmt(self, stmt);
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        walk_pattern(self, pattern);
+    }
+
+    fn visit_type_expr(&mut self, type_expr: &TypeExpr) {
+        walk_type_expr(self, type_expr);
+    }
+}
+
+pub fn walk_stmt<V: Visitor>(visitor: &mut V, stmt: &Stmt) {
+    match stmt {
+        | Stmt::Empty { span: _ }
+        | Stmt::Break { span: _ }
+        | Stmt::Continue { span: _ } => {}
+        Stmt::Binding { span: _, mutable: _, name: _, name_span: _, initializer, annotation } => {
+            if let Some(annotation) = annotation {
+                visitor.visit_type_expr(annotation);
+            }
+            if let Some(initializer) = initializer {
+                visitor.visit_expr(initializer);
+            }
+        }
+        Stmt::Expression { span: _, expr } => visitor.visit_expr(expr),
+        | Stmt::Block { span: _, body }
+        | Stmt::Function { span: _, name: _, name_span: _, parameters: _, body } => {
+            for stmt in body {
+                visitor.visit_stmt(stmt);
+            }
+        }
+        Stmt::Return { span: _, value } => {
+            if let Some(value) = value {
+                visitor.visit_expr(value);
+            }
+        }
+        Stmt::Struct { span: _, name: _, fields } => {
+            for field in fields {
+                visitor.visit_type_expr(&field.annotation);
+            }
+        }
+        Stmt::Enum { span: _, name: _, variants } => {
+            for variant in variants {
+                for type_expr in &variant.payload {
+                    visitor.visit_type_expr(type_expr);
+                }
+            }
+        }
+        Stmt::If { span: _, condition, body, else_branch } => {
+            visitor.visit_expr(condition);
+            for stmt in body {
+                visitor.visit_stmt(stmt);
+            }
+            if let Some(else_branch) = else_branch {
+                visitor.visit_stmt(else_branch);
+            }
+        }
+        Stmt::While { span: _, condition, body } => {
+            visitor.visit_expr(condition);
+            for stmt in body {
+                visitor.visit_stmt(stmt);
+            }
+        }
+    }
+}
+
+pub fn walk_expr<V: Visitor>(visitor: &mut V, expr: &Expr) {
+    match expr {
+        | Expr::Empty { span: _ }
+        | Expr::Float { span: _, value: _ }
+        | Expr::Integer { span: _, value: _ }
+        | Expr::String { span: _, value: _ }
+        | Expr::Bool { span: _, value: _ }
+        | Expr::Symbol { span: _, name: _ } => {}
+        Expr::Grouping { span: _, inner } => visitor.visit_expr(inner),
+        | Expr::Binary { span: _, lhs, rhs, op: _ }
+        | Expr::Assignment { span: _, lhs, rhs } => {
+            visitor.visit_expr(lhs);
+            visitor.visit_expr(rhs);
+        }
+        Expr::Unary { span: _, op: _, operand } => visitor.visit_expr(operand),
+        Expr::FunctionCall { span: _, callee, arguments } => {
+            visitor.visit_expr(callee);
+            for argument in arguments {
+                visitor.visit_expr(argument);
+            }
+        }
+        Expr::Array { span: _, elements } => {
+            for element in elements {
+                visitor.visit_expr(element);
+            }
+        }
+        Expr::Index { span: _, target, index } => {
+            visitor.visit_expr(target);
+            visitor.visit_expr(index);
+        }
+        Expr::Struct { span: _, name: _, fields } => {
+            for field in fields {
+                visitor.visit_expr(&field.value);
+            }
+        }
+        Expr::Field { span: _, target, name: _ } => visitor.visit_expr(target),
+        Expr::Match { span: _, scrutinee, arms } => {
+            visitor.visit_expr(scrutinee);
+            for arm in arms {
+                visitor.visit_pattern(&arm.pattern);
+                visitor.visit_expr(&arm.body);
+            }
+        }
+    }
+}
+
+pub fn walk_pattern<V: Visitor>(visitor: &mut V, pattern: &Pattern) {
+    match pattern {
+        Pattern::Variant { span: _, name: _, fields } => {
+            for field in fields {
+                visitor.visit_pattern(field);
+            }
+        }
+        | Pattern::Empty { span: _ }
+        | Pattern::Wildcard { span: _ }
+        | Pattern::Symbol { span: _, name: _ }
+        | Pattern::Integer { span: _, value: _ }
+        | Pattern::String { span: _, value: _ }
+        | Pattern::Bool { span: _, value: _ } => {}
+    }
+}
+
+pub fn walk_type_expr<V: Visitor>(visitor: &mut V, type_expr: &TypeExpr) {
+    match type_expr {
+        TypeExpr::Array { span: _, element } => visitor.visit_type_expr(element),
+        TypeExpr::Empty { span: _ } | TypeExpr::Named { span: _, name: _ } => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::tests::parse;
+
+    /// Uses every kind of node, with each expression position holding its own integer
+    const PROGRAM: &'static str =
+        "mut a: [int] = [1, 2]
+b = -3 + (4 * 5)
+a[6] -> 7
+p.x -> 8
+function f(x) {
+    return f(9)[10].y
+    return
+}
+struct P {
+    x: int
+}
+enum E {
+    V(int, [str])
+}
+if (P { x: 11 }).x == 12 {
+    c = 13
+} else if 14 {
+    f(15)
+} else {
+    while 16 {
+        break
+        continue
+    }
+}
+{
+    d = match 17 {
+        V(0, _) => 18
+        s => \"s\"
+        \"t\" => true
+        false => 2.5
+    }
+}
+";
+
+    /// Returns the name of a node's variant, e.g. `Expr::Binary`
+    fn kind(category: &str, node: &impl std::fmt::Debug) -> String {
+        let debug = format!("{:?}", node);
+        return format!("{}::{}", category, debug.split([' ', '{', '(']).next().unwrap());
+    }
+
+    /// Records every node it reaches
+    #[derive(Default)]
+    struct Recorder {
+        kinds: Vec<String>,
+        integers: Vec<i32>,
+        types: Vec<String>,
+    }
+
+    impl Recorder {
+        /// Checks that every kind of node was reached, other than the `Empty` ones that
+        /// only come from errors, and that no expression or type was missed
+        fn check(mut self) {
+            let expected = [
+                "Expr::Array", "Expr::Assignment", "Expr::Binary", "Expr::Bool", "Expr::Field",
+                "Expr::Float", "Expr::FunctionCall", "Expr::Grouping", "Expr::Index",
+                "Expr::Integer", "Expr::Match", "Expr::String", "Expr::Struct", "Expr::Symbol",
+                "Expr::Unary", "Pattern::Bool", "Pattern::Integer", "Pattern::String",
+                "Pattern::Symbol", "Pattern::Variant", "Pattern::Wildcard", "Stmt::Binding",
+                "Stmt::Block", "Stmt::Break", "Stmt::Continue", "Stmt::Enum",
+                "Stmt::Expression", "Stmt::Function", "Stmt::If", "Stmt::Return",
+                "Stmt::Struct", "Stmt::While", "TypeExpr::Array", "TypeExpr::Named",
+            ];
+            self.kinds.sort();
+            self.kinds.dedup();
+            assert_eq!(self.kinds, expected);
+
+            self.integers.sort();
+            assert_eq!(self.integers, (1..=18).collect::<Vec<i32>>());
+
+            self.types.sort();
+            assert_eq!(self.types, ["int", "int", "int", "str"]);
+        }
+    }
+
+    impl Visitor for Recorder {
+        fn visit_stmt(&mut self, stmt: &Stmt) {
+            self.kinds.push(kind("Stmt", stmt));
+            walk_stmt(self, stmt);
+        }
+
+        fn visit_expr(&mut self, expr: &Expr) {
+            self.kinds.push(kind("Expr", expr));
+            if let Expr::Integer { span: _, value } = expr {
+                self.integers.push(*value);
+            }
+            walk_expr(self, expr);
+        }
+
+        fn visit_pattern(&mut self, pattern: &Pattern) {
+            self.kinds.push(kind("Pattern", pattern));
+            walk_pattern(self, pattern);
+        }
+
+        fn visit_type_expr(&mut self, type_expr: &TypeExpr) {
+            self.kinds.push(kind("TypeExpr", type_expr));
+            if let TypeExpr::Named { span: _, name } = type_expr {
+                self.types.push(name.clone());
+            }
+            walk_type_expr(self, type_expr);
+        }
+    }
+
+    #[test]
+    fn visitor_reaches_every_node() {
+        let tree = parse(PROGRAM);
+        let mut recorder = Recorder::default();
+        for stmt in &tree {
+            recorder.visit_stmt(stmt);
+        }
+        recorder.check();
+    }
 }
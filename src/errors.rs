@@ -1,5 +1,7 @@
 use std::{ fmt::Display, io::{ stdout, Write } };
 
+use crate::source::Source;
+
 const TERM_ESC: &'static str = "\x1b[";
 const TERMCOL_ERROR: &'static str = "91m";
 const TERMCOL_MESSAGE: &'static str = "92m";
@@ -12,6 +14,10 @@ pub enum Kind {
     ParseError,
     NameError,
     TypeError,
+    UnusedBinding,
+    UnusedMut,
+    NonExhaustive,
+    UnreachablePattern,
 }
 
 impl Display for Kind {
@@ -22,6 +28,10 @@ impl Display for Kind {
             Kind::ParseError => "Parse Error",
             Kind::NameError => "Name Error",
             Kind::TypeError => "Type Error",
+            Kind::UnusedBinding => "Unused Binding",
+            Kind::UnusedMut => "Unused Mut",
+            Kind::NonExhaustive => "Non-Exhaustive Match",
+            Kind::UnreachablePattern => "Unreachable Pattern",
         };
         write!(f, "{TERM_ESC}{TERMCOL_HIGHLIGHT}{}{TERM_RESET}", error_name)
     }
@@ -33,21 +43,37 @@ pub enum Flag {
     Suggestion,
 }
 
-/// `offset` and `len` represents the start and length of the offending content, the reporter will automatically fetch the relevant line data when printing the error
+impl Display for Flag {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Flag::Abort => write!(f, "{TERM_ESC}{TERMCOL_ERROR}[ERROR]{TERM_RESET}"),
+            Flag::Warning => write!(f, "{TERM_ESC}{TERMCOL_HIGHLIGHT}[WARNING]{TERM_RESET}"),
+            Flag::Suggestion => write!(f, "{TERM_ESC}{TERMCOL_MESSAGE}[SUGGESTION]{TERM_RESET}"),
+        }
+    }
+}
+
+/// A secondary location attached to an error, such as the declaration an error refers back to
+struct Label {
+    offset: usize,
+    len: usize,
+    message: String,
+}
+
+/// `offset` and `len` represents the start and length in bytes of the offending content, the reporter will automatically fetch the relevant line data when printing the error
 pub struct CompilerError {
     pub kind: Kind,
     pub flag: Flag,
-    line: usize,
     offset: usize,
     len: usize,
     message: String,
+    labels: Vec<Label>,
 }
 
 impl CompilerError {
     pub fn new(
         kind: Kind,
         flag: Flag,
-        line: usize,
         offset: usize,
         len: usize,
         message: &str
@@ -55,60 +81,61 @@ impl CompilerError {
         CompilerError {
             kind,
             flag,
-            line,
             offset,
             len,
             message: message.to_string(),
+            labels: vec![],
         }
     }
 
-    pub fn print(&self, source: &String, path: &String) {
-        // Do some bounds checking
-        if self.offset >= source.len() {
-            panic!("ERROR out of bounds!");
-        }
-
-        // Get the start and end of this line
-        let mut ln_start = 0usize;
-        let mut ln_end = source.len() - 1;
+    /// Attaches a secondary label that is printed underneath the main error
+    pub fn with_label(mut self, offset: usize, len: usize, message: &str) -> Self {
+        self.labels.push(Label { offset, len, message: message.to_string() });
+        return self;
+    }
 
-        // Decrement backwards to find the beginning of this line
-        for (i, ch) in source.char_indices().rev() {
-            if i > self.offset {
-                continue;
-            }
+    #[cfg(test)]
+    pub fn message(&self) -> &str {
+        return &self.message;
+    }
 
-            if ch == '\n' {
-                ln_start = i + 1;
-                break;
-            }
-        }
+    /// Returns where the error is, as its offset and length
+    #[cfg(test)]
+    pub fn location(&self) -> (usize, usize) {
+        return (self.offset, self.len);
+    }
 
-        // Increment forwards to find the end of this line
-        for (i, ch) in source.char_indices() {
-            if ch == '\n' {
-                ln_end = i - 1;
-                break;
-            }
-        }
+    /// Returns the message of each label, in the order they were attached
+    #[cfg(test)]
+    pub fn labels(&self) -> Vec<&str> {
+        return self.labels.iter().map(|label| label.message.as_str()).collect();
+    }
 
-        if ln_start > ln_end {
-            panic!("ERROR start > end!");
-        }
+    /// Returns a line of `mark` under the `len` bytes at `offset`. Only the first line of a
+    /// range spanning several lines is printed, so the marks stop at its end
+    fn underline(mark: &str, source: &Source, offset: usize, len: usize) -> String {
+        let (_, ln_end) = source.line_bounds(offset);
+        let width = source.width(offset, (offset + len).min(ln_end));
+        return mark.repeat(width.max(1));
+    }
 
-        let line = source[ln_start..=ln_end].to_string();
+    pub fn print(&self, source: &Source) {
+        let (ln_start, ln_end) = source.line_bounds(self.offset);
+        let line = source.text[ln_start..ln_end].to_string();
 
         // Get the whitespace for the underline amount
-        let whitespace_len = self.offset - ln_start;
-        let whitespace = " ".repeat(whitespace_len);
-        let underline = "^".repeat(self.len);
+        let column = source.column(self.offset);
+        let whitespace = " ".repeat(column - 1);
+        let underline = Self::underline("^", source, self.offset, self.len);
 
-        // [ERROR] ../path on line 0:
+        // [ERROR] ../path:line:column Kind:
         write!(
             stdout(),
-            "\n{TERM_ESC}{TERMCOL_ERROR}[ERROR]{TERM_RESET} {}:{} {}:\n",
-            path,
-            self.line,
+            "\n{} {}:{}:{} {}:\n",
+            self.flag,
+            source.path,
+            source.line(self.offset),
+            column,
             self.kind
         ).unwrap();
 
@@ -126,11 +153,31 @@ impl CompilerError {
         // message
         write!(
             stdout(),
-            "{TERM_ESC}{TERMCOL_MESSAGE}help:{TERM_RESET} {}\n\n",
+            "{TERM_ESC}{TERMCOL_MESSAGE}help:{TERM_RESET} {}\n",
             self.message
         ).unwrap();
 
+        // ~ ../path:line:column
+        // ~ line content
+        // ~ ---- label message
+        for label in &self.labels {
+            let (ln_start, ln_end) = source.line_bounds(label.offset);
+            let column = source.column(label.offset);
+            write!(
+                stdout(),
+                "~ {}:{}:{}\n~ {}\n~ {}{TERM_ESC}{TERMCOL_MESSAGE}{} {}{TERM_RESET}\n",
+                source.path,
+                source.line(label.offset),
+                column,
+                &source.text[ln_start..ln_end],
+                " ".repeat(column - 1),
+                Self::underline("-", source, label.offset, label.len),
+                label.message
+            ).unwrap();
+        }
+
         // Flush all of this to output
+        write!(stdout(), "\n").unwrap();
         stdout().flush().unwrap();
     }
 }
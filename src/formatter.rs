@@ -0,0 +1,200 @@
+use std::collections::VecDeque;
+
+use crate::{
+    errors::CompilerError,
+    lexer::Lexer,
+    parser::Parser,
+    token::{ self, Token },
+    unparser::Unparser,
+};
+
+/// A comment from the source, which the AST has no place for
+struct Comment {
+    offset: usize,
+    text: String,
+}
+
+/// Where the source had comments and blank lines, so the unparser can put them back in
+/// between the statements it prints. A comment is printed on its own line before the
+/// statement, match arm, field or variant that follows it, unless it is on the same line
+/// as the end of the one before, where it is kept at the end of that line. Comments from
+/// inside an expression that is printed on a single line are moved to the end of it
+pub struct Layout {
+    /// Comments that haven't been printed yet, in the order they appear
+    comments: VecDeque<Comment>,
+    /// The offset of every line break outside of strings and comments
+    newlines: Vec<usize>,
+    /// The offset of every line break that ends a line with nothing on it
+    blank_lines: Vec<usize>,
+    /// The end of what was printed last
+    last: usize,
+}
+
+impl Layout {
+    /// Collects the layout of a token stream scanned with `Lexer::with_trivia`
+    pub fn new(tokens: &Vec<Token>) -> Self {
+        let mut comments: VecDeque<Comment> = VecDeque::new();
+        let mut newlines: Vec<usize> = vec![];
+        let mut blank_lines: Vec<usize> = vec![];
+
+        let mut previous: Option<&Token> = None;
+        for token in tokens {
+            let mut commented = false;
+            for trivia in &token.trivia {
+                match trivia.kind {
+                    token::TriviaKind::Whitespace => {}
+                    token::TriviaKind::LineComment | token::TriviaKind::BlockComment => {
+                        let text = trivia.text.trim_end().to_string();
+                        comments.push_back(Comment { offset: trivia.offset, text });
+                        commented = true;
+                    }
+                }
+            }
+
+            if token.kind == token::Kind::Newline {
+                let after_newline = previous.is_none_or(|t| t.kind == token::Kind::Newline);
+                if after_newline && !commented {
+                    blank_lines.push(token.offset);
+                }
+                newlines.push(token.offset);
+            }
+            previous = Some(token);
+        }
+
+        Self { comments, newlines, blank_lines, last: 0usize }
+    }
+
+    /// Returns true if the source has an empty line between `start` and `stop`
+    fn blank_between(&self, start: usize, stop: usize) -> bool {
+        return self.blank_lines.iter().any(|offset| start <= *offset && *offset < stop);
+    }
+
+    /// Returns true if the source has no line break between `start` and `stop`
+    fn same_line(&self, start: usize, stop: usize) -> bool {
+        return !self.newlines.iter().any(|offset| start <= *offset && *offset < stop);
+    }
+
+    /// Returns true if there is a comment left to print before `offset`
+    pub fn has_comment_before(&self, offset: usize) -> bool {
+        return self.comments.front().is_some_and(|comment| comment.offset < offset);
+    }
+
+    /// Takes the comments before `offset`, along with whether each one had an empty line
+    /// before it
+    pub fn comments_before(&mut self, offset: usize) -> Vec<(bool, String)> {
+        let mut comments: Vec<(bool, String)> = vec![];
+        while self.has_comment_before(offset) {
+            let comment = self.comments.pop_front().unwrap();
+            comments.push((self.blank_between(self.last, comment.offset), comment.text));
+            self.last = comment.offset;
+        }
+        return comments;
+    }
+
+    /// Returns whether there was an empty line before something starting at `offset`,
+    /// which is printed next
+    pub fn begin(&mut self, offset: usize) -> bool {
+        let blank = self.blank_between(self.last, offset);
+        self.last = offset;
+        return blank;
+    }
+
+    /// Takes the comments on the same line as `end`, where something that was just
+    /// printed ends, and before `bound`
+    pub fn trailing(&mut self, end: usize, bound: usize) -> Vec<String> {
+        let mut comments: Vec<String> = vec![];
+        while let Some(comment) = self.comments.front() {
+            if comment.offset >= bound || !self.same_line(end, comment.offset) {
+                break;
+            }
+            comments.push(self.comments.pop_front().unwrap().text);
+        }
+        self.last = self.last.max(end);
+        return comments;
+    }
+}
+
+/// Formats Chao source, or returns the errors that stopped it from being parsed
+pub fn format(source: &String) -> Result<String, Vec<CompilerError>> {
+    let mut lexer = Lexer::new(source).with_trivia();
+    lexer.scan();
+    if !lexer.errors.is_empty() {
+        return Err(lexer.errors);
+    }
+
+    let layout = Layout::new(&lexer.output);
+    let mut parser = Parser::new(std::mem::take(&mut lexer.output));
+    parser.parse();
+    if !parser.errors.is_empty() {
+        return Err(parser.errors);
+    }
+
+    let mut unparser = Unparser::new().with_layout(layout);
+    unparser.unparse(&parser.tree);
+    return Ok(unparser.output);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn formatted(source: &str) -> String {
+        match format(&source.to_string()) {
+            Ok(formatted) => return formatted,
+            Err(_) => panic!("{} has errors", source),
+        }
+    }
+
+    #[test]
+    fn keeps_comments() {
+        let source = "// leading
+x   =  1 // after x
+/* a block
+   comment */
+function f(a) {
+    return a*2   /* inline */
+}
+enum E {
+    // first
+    A
+    B // second
+}
+// trailing
+";
+        let expected = "// leading
+x = 1 // after x
+/* a block
+   comment */
+function f(a) {
+    return a * 2 /* inline */
+}
+enum E {
+    // first
+    A
+    B // second
+}
+// trailing
+";
+        assert_eq!(formatted(source), expected);
+    }
+
+    #[test]
+    fn normalizes_blank_lines() {
+        // Runs of blank lines become one, and none are kept at the start or end of a block
+        let source = "\n\nx = 1\n\n\n\ny = 2\nfunction f() {\n\n    return 1\n\n}\n\n\n";
+        assert_eq!(formatted(source), "x = 1\n\ny = 2\nfunction f() {\n    return 1\n}\n");
+    }
+
+    #[test]
+    fn is_idempotent() {
+        let sources = [
+            "x=(a+b)*c // c\n\n\n/* d */ y = -(-x)",
+            "if a {\n// only a comment\n}\nm = match a {\n  1 => 2 // two\n\n  _ => 3\n}",
+            "struct P { x: int /* x */\n y: int }\n\n\nfunction f() { return P { x: 1, y: 2 } }",
+        ];
+        for source in sources {
+            let once = formatted(source);
+            assert_eq!(formatted(&once), once, "formatting {} again changes it", source);
+        }
+    }
+}
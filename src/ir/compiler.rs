@@ -1,11 +1,22 @@
-use crate::ast::{ AstOp, Expr, Stmt };
+use std::collections::HashMap;
 
-use super::node::Node;
+use crate::ast::{ self, walk_expr, walk_pattern, walk_stmt, AstOp, Expr, Span, Stmt, Visitor };
+
+use super::node::{ Node, Pattern };
 
 pub struct Compiler {
     node_stack: Vec<Node>,
+    /// The patterns of the match arms being compiled, like `node_stack` is for expressions
+    patterns: Vec<Pattern>,
     ast: Vec<Stmt>,
     cursor: usize,
+    labels: usize,
+    /// The header and exit labels of each loop enclosing the current statement
+    loops: Vec<(usize, usize)>,
+    /// The offset of each field access within its struct, as found by the resolver
+    offsets: HashMap<Span, usize>,
+    /// The tag of each variant constructor and pattern, as found by the resolver
+    tags: HashMap<Span, usize>,
 }
 
 impl Compiler {
@@ -19,58 +30,298 @@ impl Compiler {
 }
 
 impl Compiler {
-    pub fn new(ast: Vec<Stmt>) -> Self {
+    pub fn new(
+        ast: Vec<Stmt>,
+        offsets: HashMap<Span, usize>,
+        tags: HashMap<Span, usize>
+    ) -> Self {
         Compiler {
             node_stack: vec![],
+            patterns: vec![],
             ast,
             cursor: 0usize,
+            labels: 0usize,
+            loops: vec![],
+            offsets,
+            tags,
         }
     }
 
+    /// Returns the offset of the field named at `span`. The resolver has already
+    /// reported any field that doesn't exist
+    fn offset(&self, span: &Span) -> usize {
+        return self.offsets.get(span).copied().unwrap_or(0);
+    }
+
+    /// Returns a fresh id for a jump target
+    fn label(&mut self) -> usize {
+        self.labels += 1;
+        return self.labels - 1;
+    }
+
     pub fn compile(&mut self) {
-        let ast: Vec<Stmt> = self.ast.drain(0..).collect();
-        for stmt in ast {
-            self.compile_stmt(stmt);
+        let ast = std::mem::take(&mut self.ast);
+        for stmt in &ast {
+            self.visit_stmt(stmt);
         }
 
         println!("Compiled IR:\n{:#?}", self.node_stack);
     }
 
-    fn compile_stmt(&mut self, stmt: Stmt) {
+    /// Compiles a list of statements on their own stack so that their nodes
+    /// don't mix with the nodes of the enclosing statement
+    fn compile_body(&mut self, body: &Vec<Stmt>) -> Vec<Node> {
+        let outer = std::mem::replace(&mut self.node_stack, vec![]);
+        for stmt in body {
+            self.visit_stmt(stmt);
+        }
+        return std::mem::replace(&mut self.node_stack, outer);
+    }
+
+    fn pop(&mut self) -> Box<Node> {
+        return Box::new(self.node_stack.pop().unwrap());
+    }
+
+    /// Pops the nodes of the last `count` expressions compiled, in the order they came in
+    fn pop_many(&mut self, count: usize) -> Vec<Node> {
+        return self.node_stack.split_off(self.node_stack.len() - count);
+    }
+}
+
+/// Statements are compiled in source order, as the labels they take are numbered in that
+/// order. Expressions and patterns are compiled bottom up, so their walk leaves the nodes of
+/// their children on the stack, in order, for them to be built from
+impl Visitor for Compiler {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
         match stmt {
-            Stmt::Binding { span: _, mutable, name, initializer, annotation } => {
-                let value = initializer.unwrap_or_else(|| { unimplemented!() });
-                self.compile_expr(*value);
+            Stmt::Binding { span: _, mutable, name, name_span: _, initializer, annotation: _ } => {
+                let value = initializer.as_ref().unwrap_or_else(|| { unimplemented!() });
+                self.visit_expr(value);
 
-                let value = self.node_stack.pop().unwrap();
-                if mutable {
-                    self.node_stack.push(Node::StoreMut { symbol: name, value: Box::new(value) });
+                let value = self.pop();
+                let symbol = name.clone();
+                if *mutable {
+                    self.node_stack.push(Node::StoreMut { symbol, value });
                 } else {
-                    self.node_stack.push(Node::StoreConst { symbol: name, value: Box::new(value) });
+                    self.node_stack.push(Node::StoreConst { symbol, value });
                 }
             }
-            _ => unimplemented!(),
+            Stmt::Function { span: _, name, name_span: _, parameters, body } => {
+                let loops = std::mem::replace(&mut self.loops, vec![]);
+                let body = self.compile_body(body);
+                self.loops = loops;
+
+                let parameters = parameters
+                    .iter()
+                    .map(|parameter| parameter.name.clone())
+                    .collect();
+                self.node_stack.push(Node::Function { name: name.clone(), parameters, body });
+            }
+            Stmt::Return { span: _, value } => {
+                walk_stmt(self, stmt);
+                let value = value.as_ref().map(|_| self.pop());
+                self.node_stack.push(Node::Return { value });
+            }
+            Stmt::If { span: _, condition, body, else_branch } => {
+                self.visit_expr(condition);
+                let condition = self.pop();
+
+                // JumpIfFalse else ; body ; Jump end ; else: ... ; end:
+                let else_label = self.label();
+                self.node_stack.push(Node::JumpIfFalse { condition, target: else_label });
+
+                let body = self.compile_body(body);
+                self.node_stack.push(Node::Block { body });
+
+                match else_branch {
+                    Some(else_branch) => {
+                        let end_label = self.label();
+                        self.node_stack.push(Node::Jump { target: end_label });
+                        self.node_stack.push(Node::Label { id: else_label });
+                        self.visit_stmt(else_branch);
+                        self.node_stack.push(Node::Label { id: end_label });
+                    }
+                    None => self.node_stack.push(Node::Label { id: else_label }),
+                }
+            }
+            Stmt::While { span: _, condition, body } => {
+                // header: JumpIfFalse exit ; body ; Jump header ; exit:
+                let header = self.label();
+                let exit = self.label();
+                self.node_stack.push(Node::Label { id: header });
+
+                self.visit_expr(condition);
+                let condition = self.pop();
+                self.node_stack.push(Node::JumpIfFalse { condition, target: exit });
+
+                self.loops.push((header, exit));
+                let body = self.compile_body(body);
+                self.loops.pop();
+
+                self.node_stack.push(Node::Block { body });
+                self.node_stack.push(Node::Jump { target: header });
+                self.node_stack.push(Node::Label { id: exit });
+            }
+            Stmt::Break { span: _ } => {
+                if let Some((_, exit)) = self.loops.last() {
+                    self.node_stack.push(Node::Jump { target: *exit });
+                }
+            }
+            Stmt::Continue { span: _ } => {
+                if let Some((header, _)) = self.loops.last() {
+                    self.node_stack.push(Node::Jump { target: *header });
+                }
+            }
+            Stmt::Block { span: _, body } => {
+                let body = self.compile_body(body);
+                self.node_stack.push(Node::Block { body });
+            }
+            Stmt::Struct { span: _, name, fields } => {
+                let fields = fields
+                    .iter()
+                    .map(|field| field.name.clone())
+                    .collect();
+                self.node_stack.push(Node::Struct { name: name.clone(), fields });
+            }
+            Stmt::Enum { span: _, name, variants } => {
+                let variants = variants
+                    .iter()
+                    .map(|variant| (variant.name.clone(), variant.payload.len()))
+                    .collect();
+                self.node_stack.push(Node::Enum { name: name.clone(), variants });
+            }
+            Stmt::Expression { span: _, expr: _ } | Stmt::Empty { span: _ } => {
+                walk_stmt(self, stmt);
+            }
         }
     }
 
-    fn compile_expr(&mut self, expr: Expr) {
-        match expr {
-            Expr::Integer { span: _, value } => self.node_stack.push(Node::Integer(value)),
-            Expr::Symbol { span: _, name } => self.node_stack.push(Node::Symbol(name)),
+    fn visit_expr(&mut self, expr: &Expr) {
+        walk_expr(self, expr);
+
+        let node = match expr {
+            Expr::Integer { span: _, value } => Node::Integer(*value),
+            Expr::Float { span: _, value } => Node::Float(*value),
+            Expr::String { span: _, value } => Node::String(value.clone()),
+            Expr::Bool { span: _, value } => Node::Bool(*value),
+            Expr::Symbol { span, name } => {
+                match self.tags.get(span) {
+                    Some(tag) => Node::Variant { tag: *tag, payload: vec![] },
+                    None => Node::Symbol(name.clone()),
+                }
+            }
 
-            Expr::Binary { span: _, lhs, rhs, op } => {
+            Expr::Binary { span: _, lhs: _, rhs: _, op } => {
+                let rhs = self.pop();
+                let lhs = self.pop();
+                match op {
+                    AstOp::Plus => Node::Add { lhs, rhs },
+                    AstOp::Minus => Node::Subtract { lhs, rhs },
+                    AstOp::Multiply => Node::Multiply { lhs, rhs },
+                    AstOp::Divide => Node::Divide { lhs, rhs },
+                    AstOp::Modulus => Node::Modulus { lhs, rhs },
+                    AstOp::Equal => Node::Equal { lhs, rhs },
+                    AstOp::NotEqual => Node::NotEqual { lhs, rhs },
+                    AstOp::Less => Node::Less { lhs, rhs },
+                    AstOp::LessEqual => Node::LessEqual { lhs, rhs },
+                    AstOp::Greater => Node::Greater { lhs, rhs },
+                    AstOp::GreaterEqual => Node::GreaterEqual { lhs, rhs },
+                    _ => unreachable!("{} is not a binary operator", op),
+                }
+            }
+            // The walk has already left the node of what it groups
+            Expr::Grouping { span: _, inner: _ } => return,
+            Expr::Unary { span: _, op, operand: _ } => {
+                let operand = self.pop();
                 match op {
-                    AstOp::Plus => {
-                        self.compile_expr(*lhs);
-                        self.compile_expr(*rhs);
-                        let rhs = self.node_stack.pop().unwrap();
-                        let lhs = self.node_stack.pop().unwrap();
-                        self.node_stack.push(Node::Add { lhs: Box::new(lhs), rhs: Box::new(rhs) });
+                    AstOp::Negate => Node::Negate { operand },
+                    AstOp::Not => Node::Not { operand },
+                    _ => unreachable!("{} is not a unary operator", op),
+                }
+            }
+            Expr::Assignment { span: _, lhs: _, rhs: _ } => {
+                // The target was compiled as a load, which is turned into a store
+                let value = self.pop();
+                match *self.pop() {
+                    Node::Symbol(symbol) => Node::Reassign { symbol, value },
+                    Node::ArrayLoad { array, index } => Node::ArrayStore { array, index, value },
+                    Node::FieldLoad { object, offset } => {
+                        Node::FieldStore { object, offset, value }
                     }
-                    _ => unimplemented!(),
+                    // The resolver rejects every other target
+                    _ => unreachable!("only a symbol, index or field can be reassigned"),
                 }
             }
-            _ => unimplemented!(),
-        }
+            Expr::FunctionCall { span: _, callee: _, arguments } => {
+                let arguments = self.pop_many(arguments.len());
+                match *self.pop() {
+                    // Calling a variant builds it rather than calling a function
+                    Node::Variant { tag, payload: _ } => Node::Variant { tag, payload: arguments },
+                    callee => Node::Call { callee: Box::new(callee), arguments },
+                }
+            }
+            Expr::Array { span: _, elements } => {
+                Node::ArrayAlloc { elements: self.pop_many(elements.len()) }
+            }
+            Expr::Index { span: _, target: _, index: _ } => {
+                let index = self.pop();
+                let array = self.pop();
+                Node::ArrayLoad { array, index }
+            }
+            Expr::Struct { span: _, name, fields } => {
+                let values = self.pop_many(fields.len());
+                let mut values: Vec<(usize, Node)> = fields
+                    .iter()
+                    .map(|field| self.offset(&field.span))
+                    .zip(values)
+                    .collect();
+                values.sort_by_key(|(offset, _)| *offset);
+
+                let fields = values
+                    .into_iter()
+                    .map(|(_, node)| node)
+                    .collect();
+                Node::StructAlloc { name: name.clone(), fields }
+            }
+            Expr::Field { span, target: _, name: _ } => {
+                let object = self.pop();
+                Node::FieldLoad { object, offset: self.offset(span) }
+            }
+            Expr::Match { span: _, scrutinee: _, arms } => {
+                let bodies = self.pop_many(arms.len());
+                let patterns = self.patterns.split_off(self.patterns.len() - arms.len());
+                let scrutinee = self.pop();
+                Node::Match { scrutinee, arms: patterns.into_iter().zip(bodies).collect() }
+            }
+            // Only a program without errors is compiled, and the parser reports an error
+            // everywhere it leaves one of these
+            Expr::Empty { span: _ } => unreachable!("the parser left an empty expression"),
+        };
+        self.node_stack.push(node);
+    }
+
+    fn visit_pattern(&mut self, pattern: &ast::Pattern) {
+        walk_pattern(self, pattern);
+
+        let pattern = match pattern {
+            | ast::Pattern::Empty { span: _ }
+            | ast::Pattern::Wildcard { span: _ } => Pattern::Wildcard,
+            ast::Pattern::Symbol { span, name } => {
+                match self.tags.get(span) {
+                    Some(tag) => Pattern::Variant { tag: *tag, fields: vec![] },
+                    None => Pattern::Bind(name.clone()),
+                }
+            }
+            ast::Pattern::Variant { span, name: _, fields } => {
+                let fields = self.patterns.split_off(self.patterns.len() - fields.len());
+                // The resolver has already reported any variant that doesn't exist
+                let tag = self.tags.get(span).copied().unwrap_or(0);
+                Pattern::Variant { tag, fields }
+            }
+            ast::Pattern::Integer { span: _, value } => Pattern::Integer(*value),
+            ast::Pattern::String { span: _, value } => Pattern::String(value.clone()),
+            ast::Pattern::Bool { span: _, value } => Pattern::Bool(*value),
+        };
+        self.patterns.push(pattern);
     }
 }
@@ -1,6 +1,23 @@
+/// A pattern of a match arm, with variants referred to by their tag
+#[derive(Debug)]
+pub enum Pattern {
+    Wildcard,
+    Bind(String),
+    Variant {
+        tag: usize,
+        fields: Vec<Pattern>,
+    },
+    Integer(i32),
+    String(String),
+    Bool(bool),
+}
+
 #[derive(Debug)]
 pub enum Node {
     Integer(i32),
+    Float(f64),
+    String(String),
+    Bool(bool),
     Symbol(String),
 
     StoreMut {
@@ -11,8 +28,138 @@ pub enum Node {
         symbol: String,
         value: Box<Node>,
     },
+    Reassign {
+        symbol: String,
+        value: Box<Node>,
+    },
     Add {
         lhs: Box<Node>,
         rhs: Box<Node>,
     },
+    Subtract {
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+    Multiply {
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+    Divide {
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+    Modulus {
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+    Equal {
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+    NotEqual {
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+    Less {
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+    LessEqual {
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+    Greater {
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+    GreaterEqual {
+        lhs: Box<Node>,
+        rhs: Box<Node>,
+    },
+
+    Negate {
+        operand: Box<Node>,
+    },
+    Not {
+        operand: Box<Node>,
+    },
+
+    Function {
+        name: String,
+        parameters: Vec<String>,
+        body: Vec<Node>,
+    },
+    Call {
+        callee: Box<Node>,
+        arguments: Vec<Node>,
+    },
+    Return {
+        value: Option<Box<Node>>,
+    },
+
+    /// Allocates an array holding `elements` in order
+    ArrayAlloc {
+        elements: Vec<Node>,
+    },
+    ArrayLoad {
+        array: Box<Node>,
+        index: Box<Node>,
+    },
+    ArrayStore {
+        array: Box<Node>,
+        index: Box<Node>,
+        value: Box<Node>,
+    },
+
+    /// The layout of a struct, each field is stored at the offset of its position here
+    Struct {
+        name: String,
+        fields: Vec<String>,
+    },
+    /// Allocates a struct holding `fields` in layout order
+    StructAlloc {
+        name: String,
+        fields: Vec<Node>,
+    },
+    FieldLoad {
+        object: Box<Node>,
+        offset: usize,
+    },
+    FieldStore {
+        object: Box<Node>,
+        offset: usize,
+        value: Box<Node>,
+    },
+
+    /// The layout of an enum, each variant's tag is its position here along with
+    /// the number of values in its payload
+    Enum {
+        name: String,
+        variants: Vec<(String, usize)>,
+    },
+    /// Allocates a variant holding `payload`
+    Variant {
+        tag: usize,
+        payload: Vec<Node>,
+    },
+    /// Evaluates the body of the first arm whose pattern matches `scrutinee`
+    Match {
+        scrutinee: Box<Node>,
+        arms: Vec<(Pattern, Node)>,
+    },
+
+    Block {
+        body: Vec<Node>,
+    },
+
+    Label {
+        id: usize,
+    },
+    Jump {
+        target: usize,
+    },
+    JumpIfFalse {
+        condition: Box<Node>,
+        target: usize,
+    },
 }
@@ -1,11 +1,18 @@
-use crate::{ errors::{ self, CompilerError }, token::{ self, Token } };
+use crate::{ errors::{ self, CompilerError }, token::{ self, Token, Trivia } };
 
 pub struct Lexer {
     pub output: Vec<Token>,
     pub errors: Vec<CompilerError>,
+    /// The source as characters, `cursor` and the starts of tokens index into this
     stream: Vec<char>,
+    /// The byte offset in the source of each character in `stream`, followed by the
+    /// length of the source, which is what positions outside of the lexer are given in
+    offsets: Vec<usize>,
     cursor: usize,
-    line: usize,
+    /// Whether to keep whitespace and comments, attaching them to the token that follows
+    keep_trivia: bool,
+    /// Trivia seen since the last token was pushed
+    trivia: Vec<Trivia>,
 }
 
 impl Lexer {
@@ -28,14 +35,214 @@ impl Lexer {
         return self.stream[start..=self.cursor].iter().collect();
     }
 
+    /// Returns the byte offset of the character at `index` in the stream
+    fn offset(&self, index: usize) -> usize {
+        return self.offsets[index.min(self.stream.len())];
+    }
+
+    /// Returns the length in bytes of the characters from `start` to the cursor
+    fn len(&self, start: usize) -> usize {
+        return self.offset(self.cursor + 1) - self.offset(start);
+    }
+
     fn token(&mut self, kind: token::Kind, start: usize) {
         self.output.push(Token {
             kind: kind,
-            offset: start,
-            line: self.line,
+            offset: self.offset(start),
+            len: self.len(start),
             lexeme: self.lexeme(start),
+            trivia: std::mem::take(&mut self.trivia),
+        });
+    }
+
+    /// Records the whitespace or comment from `start` to the cursor, if trivia is being kept
+    fn push_trivia(&mut self, kind: token::TriviaKind, start: usize) {
+        if self.keep_trivia {
+            let text = self.lexeme(start);
+            self.trivia.push(Trivia { kind, offset: self.offset(start), text });
+        }
+    }
+
+    /// Pushes `then` if the next character is `next` (consuming it), otherwise pushes `otherwise`
+    fn push_if_next_else(
+        &mut self,
+        next: char,
+        start: usize,
+        then: token::Kind,
+        otherwise: token::Kind
+    ) {
+        if self.peek() == &next {
+            self.cursor += 1;
+            self.token(then, start);
+        } else {
+            self.token(otherwise, start);
+        }
+    }
+
+    /// Scans a string literal starting at the opening '"', decoding escape sequences
+    /// into the token's lexeme. The cursor is left on the closing '"'
+    fn string(&mut self, start: usize) {
+        let mut value = String::new();
+
+        loop {
+            self.cursor += 1;
+            match *self.current() {
+                '"' => {
+                    break;
+                }
+                '\\' => {
+                    if let Some(ch) = self.escape() {
+                        value.push(ch);
+                    }
+                }
+                '\0' => {
+                    self.errors.push(
+                        CompilerError::new(
+                            errors::Kind::UnterminatedLiteral,
+                            errors::Flag::Abort,
+                            self.offset(start),
+                            self.offset(self.stream.len()) - self.offset(start),
+                            "this string literal has no ending '\"'"
+                        )
+                    );
+                    self.cursor = self.stream.len() - 1;
+                    break;
+                }
+                ch => value.push(ch),
+            }
+        }
+
+        self.output.push(Token {
+            kind: token::Kind::String,
+            offset: self.offset(start),
+            len: self.len(start),
+            lexeme: value,
+            trivia: std::mem::take(&mut self.trivia),
         });
     }
+
+    /// Decodes the escape sequence beginning at the '\\' under the cursor, leaving
+    /// the cursor on its last character. Returns `None` and reports an error
+    /// pointing at the sequence if it is not valid
+    fn escape(&mut self) -> Option<char> {
+        let start = self.cursor;
+        self.cursor += 1;
+
+        let ch = match *self.current() {
+            'n' => '\n',
+            't' => '\t',
+            'r' => '\r',
+            '0' => '\0',
+            '\\' => '\\',
+            '"' => '"',
+            '\'' => '\'',
+            'u' => {
+                return self.unicode_escape(start);
+            }
+            '\0' if self.cursor >= self.stream.len() => {
+                // Let the string literal report the missing '"'
+                self.cursor -= 1;
+                return None;
+            }
+            other => {
+                self.errors.push(
+                    CompilerError::new(
+                        errors::Kind::SyntaxError,
+                        errors::Flag::Abort,
+                        self.offset(start),
+                        self.len(start),
+                        format!("unknown escape sequence '\\{}'", other.escape_default()).as_str()
+                    )
+                );
+                return None;
+            }
+        };
+
+        return Some(ch);
+    }
+
+    /// Decodes a `\u{XXXX}` escape, where `start` is the offset of the '\\'
+    fn unicode_escape(&mut self, start: usize) -> Option<char> {
+        let mut digits = String::new();
+        let mut valid = self.peek() == &'{';
+
+        if valid {
+            self.cursor += 1;
+            while self.peek().is_ascii_hexdigit() {
+                self.cursor += 1;
+                digits.push(*self.current());
+            }
+
+            if self.peek() == &'}' {
+                self.cursor += 1;
+            } else {
+                valid = false;
+            }
+        }
+
+        let ch = u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32);
+        if !valid || digits.len() > 6 || ch.is_none() {
+            self.errors.push(
+                CompilerError::new(
+                    errors::Kind::SyntaxError,
+                    errors::Flag::Abort,
+                    self.offset(start),
+                    self.len(start),
+                    "invalid unicode escape, expected 1 to 6 hex digits of a valid code point like '\\u{1F600}'"
+                )
+            );
+            return None;
+        }
+
+        return ch;
+    }
+
+    /// Skips a `//` comment, leaving the cursor on its last character so the
+    /// terminating newline is still tokenized
+    fn line_comment(&mut self) {
+        while self.peek() != &'\n' && self.peek() != &'\0' {
+            self.cursor += 1;
+        }
+    }
+
+    /// Skips a `/* ... */` comment, which may be nested and span multiple lines.
+    /// The cursor is left on the closing '/' of the outermost comment
+    fn block_comment(&mut self, start: usize) {
+        let mut depth = 0usize;
+
+        loop {
+            match (self.current(), self.peek()) {
+                ('/', '*') => {
+                    depth += 1;
+                    self.cursor += 2;
+                }
+                ('*', '/') => {
+                    depth -= 1;
+                    self.cursor += 1;
+                    if depth == 0 {
+                        return;
+                    }
+                    self.cursor += 1;
+                }
+                ('\0', _) => {
+                    self.errors.push(
+                        CompilerError::new(
+                            errors::Kind::UnterminatedLiteral,
+                            errors::Flag::Abort,
+                            self.offset(start),
+                            self.offset(start + 2) - self.offset(start),
+                            "this block comment has no ending '*/'"
+                        )
+                    );
+                    self.cursor = self.stream.len() - 1;
+                    return;
+                }
+                _ => {
+                    self.cursor += 1;
+                }
+            }
+        }
+    }
 }
 
 impl Lexer {
@@ -43,19 +250,30 @@ impl Lexer {
         let mut l = Lexer {
             stream: vec![],
             output: vec![],
+            offsets: vec![],
             cursor: 0usize,
-            line: 1usize,
             errors: vec![],
+            keep_trivia: false,
+            trivia: vec![],
         };
         l.load_string(string);
         return l;
     }
 
+    /// Keeps whitespace and comments as the `trivia` of the token after them, for tools
+    /// like the formatter that need to reproduce more than the parser sees
+    pub fn with_trivia(mut self) -> Self {
+        self.keep_trivia = true;
+        return self;
+    }
+
     /// Clears the `stream` field of the lexer and reads in
     /// a new string as a `Vec<char>`
     fn load_string(&mut self, string: &String) {
         self.stream.clear();
         self.stream = string.chars().collect();
+        self.offsets = string.char_indices().map(|(offset, _)| offset).collect();
+        self.offsets.push(string.len());
     }
 
     pub fn print_tokens(&self) {
@@ -69,12 +287,13 @@ impl Lexer {
             match self.current() {
                 // Handle whitespace and newlines here
                 ' ' | '\t' | '\r' => {
-                    self.cursor += 1;
-                    continue 'scan;
+                    while matches!(self.peek(), ' ' | '\t' | '\r') {
+                        self.cursor += 1;
+                    }
+                    self.push_trivia(token::TriviaKind::Whitespace, start);
                 }
                 '\n' => {
                     self.token(token::Kind::Newline, start);
-                    self.line += 1;
                 }
 
                 // Grouping operators
@@ -88,9 +307,23 @@ impl Lexer {
                 // Arithmetic operators
                 '+' => self.token(token::Kind::Plus, start),
                 '*' => self.token(token::Kind::Star, start),
-                '/' => self.token(token::Kind::Slash, start),
                 '%' => self.token(token::Kind::Modulo, start),
 
+                // Slashes can either be division or the start of a comment
+                '/' => {
+                    match self.peek() {
+                        '/' => {
+                            self.line_comment();
+                            self.push_trivia(token::TriviaKind::LineComment, start);
+                        }
+                        '*' => {
+                            self.block_comment(start);
+                            self.push_trivia(token::TriviaKind::BlockComment, start);
+                        }
+                        _ => self.token(token::Kind::Slash, start),
+                    }
+                }
+
                 '-' => {
                     match self.peek() {
                         '>' => {
@@ -101,34 +334,34 @@ impl Lexer {
                     }
                 }
 
-                '=' => self.token(token::Kind::Equal, start),
+                // Comparison and equality operators
+                '!' => self.push_if_next_else('=', start, token::Kind::BangEqual, token::Kind::Bang),
+                '=' => {
+                    match self.peek() {
+                        '>' => {
+                            self.cursor += 1;
+                            self.token(token::Kind::FatArrow, start);
+                        }
+                        _ =>
+                            self.push_if_next_else(
+                                '=',
+                                start,
+                                token::Kind::EqualEqual,
+                                token::Kind::Equal
+                            ),
+                    }
+                }
+                '<' => self.push_if_next_else('=', start, token::Kind::LessEqual, token::Kind::Less),
+                '>' => self.push_if_next_else('=', start, token::Kind::MoreEqual, token::Kind::More),
 
                 // Miscellaneous symbols
                 ',' => self.token(token::Kind::Comma, start),
-
-                // '!' => self.push_if_next_else('=', start, Token::Kind::BangEqual, Token::Kind::Bang),
-                // '=' => self.push_if_next_else('=', start, Token::Kind::EqualEqual, Token::Kind::Equal),
+                ';' => self.token(token::Kind::Semicolon, start),
+                ':' => self.token(token::Kind::Colon, start),
+                '.' => self.token(token::Kind::Dot, start),
 
                 // Literals
-                '"' => {
-                    'str_literal: while self.peek() != &'"' {
-                        if self.peek() == &'\0' {
-                            self.errors.push(
-                                CompilerError::new(
-                                    errors::Kind::UnterminatedLiteral,
-                                    errors::Flag::Abort,
-                                    self.line,
-                                    start,
-                                    self.stream.len() - start,
-                                    "this string literal has no ending '\"'"
-                                )
-                            );
-                            break 'str_literal;
-                        }
-                        self.cursor += 1;
-                    }
-                    self.token(token::Kind::String, start + 1);
-                }
+                '"' => self.string(start),
 
                 'a'..='z' | 'A'..='Z' | '_' => {
                     while self.peek().is_alphanumeric() || self.peek() == &'_' {
@@ -141,9 +374,10 @@ impl Lexer {
                         // If so, push the keyword
                         self.output.push(Token {
                             kind,
-                            offset: start,
-                            line: self.line,
+                            offset: self.offset(start),
+                            len: self.len(start),
                             lexeme,
+                            trivia: std::mem::take(&mut self.trivia),
                         });
                     } else {
                         // Otherwise, push a symbol
@@ -180,9 +414,8 @@ impl Lexer {
                         CompilerError::new(
                             errors::Kind::SyntaxError,
                             errors::Flag::Abort,
-                            self.line,
-                            self.cursor,
-                            1,
+                            self.offset(start),
+                            self.len(start),
                             "illegal character"
                         )
                     );
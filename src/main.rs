@@ -5,12 +5,16 @@ use ast::Stmt;
 use ir::compiler::Compiler;
 use lexer::Lexer;
 use parser::Parser;
+use source::Source;
 
 mod token;
+mod source;
 mod lexer;
 mod ast;
 mod errors;
 mod parser;
+mod unparser;
+mod formatter;
 mod assembler;
 mod ir;
 mod analysis;
@@ -20,6 +24,11 @@ const FILE_PATH: &'static str = "main.chao";
 fn main() {
     let args: Vec<String> = env::args().collect();
 
+    if args.get(1).map(String::as_str) == Some("fmt") {
+        fmt(&args[2..]);
+        return;
+    }
+
     let path: &String = {
         if args.len() >= 2 {
             &args[1]
@@ -35,17 +44,23 @@ fn main() {
         std::process::exit(1);
     });
 
+    let source = Source::new(path.clone(), file);
+
     println!("File successfully fetched:");
-    println!("{file}");
+    println!("{}", source.text);
 
     // Create lexer and load the source code
-    let mut lexer = Lexer::new(&file);
+    let mut lexer = Lexer::new(&source.text);
     lexer.scan();
 
     lexer.print_tokens();
 
+    // Whether any stage found an error that stops the program from being compiled
+    let mut aborted = false;
+
     for error in lexer.errors {
-        error.print(&file, &path);
+        aborted = aborted || matches!(error.flag, errors::Flag::Abort);
+        error.print(&source);
     }
 
     let token_stream = mem::replace(&mut lexer.output, Vec::new());
@@ -53,7 +68,8 @@ fn main() {
     parser.parse();
 
     for error in parser.errors {
-        error.print(&file, &path);
+        aborted = aborted || matches!(error.flag, errors::Flag::Abort);
+        error.print(&source);
     }
 
     let mut ast: Vec<Stmt> = vec![];
@@ -61,11 +77,65 @@ fn main() {
 
     let mut resolver = Resolver::new();
     resolver.resolve_names(&ast);
+    let offsets = mem::take(&mut resolver.offsets);
+    let tags = mem::take(&mut resolver.tags);
 
     for error in resolver.errors {
-        error.print(&file, &path);
+        aborted = aborted || matches!(error.flag, errors::Flag::Abort);
+        error.print(&source);
     }
 
-    let mut compiler = Compiler::new(ast);
+    if aborted {
+        std::process::exit(1);
+    }
+
+    let mut compiler = Compiler::new(ast, offsets, tags);
     compiler.compile();
 }
+
+/// `fmt [--check] <path>...` rewrites each file in the canonical style. With `--check`
+/// nothing is written, and it exits with an error if any file isn't formatted already
+fn fmt(args: &[String]) {
+    let check = args.iter().any(|arg| arg == "--check");
+    let paths: Vec<&String> = args
+        .iter()
+        .filter(|arg| *arg != "--check")
+        .collect();
+    if paths.is_empty() {
+        eprintln!("Usage: fmt [--check] <path>...");
+        std::process::exit(2);
+    }
+
+    let mut failed = false;
+    for path in paths {
+        let file = fs::read_to_string(path).unwrap_or_else(|_| {
+            eprintln!("Error reading file from path: {}", path);
+            std::process::exit(1);
+        });
+
+        match formatter::format(&file) {
+            Ok(formatted) if formatted == file => {}
+            Ok(_) if check => {
+                println!("{} is not formatted", path);
+                failed = true;
+            }
+            Ok(formatted) => {
+                fs::write(path, formatted).unwrap_or_else(|_| {
+                    eprintln!("Error writing file to path: {}", path);
+                    std::process::exit(1);
+                });
+            }
+            Err(errors) => {
+                let source = Source::new(path.clone(), file);
+                for error in errors {
+                    error.print(&source);
+                }
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+}
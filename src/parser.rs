@@ -1,5 +1,18 @@
 use crate::{
-    ast::{ AstOp, Expr, Span, Stmt },
+    ast::{
+        Associativity,
+        AstOp,
+        Expr,
+        Field,
+        FieldInit,
+        MatchArm,
+        Parameter,
+        Pattern,
+        Span,
+        Stmt,
+        TypeExpr,
+        Variant,
+    },
     errors::{ self, CompilerError },
     token::{ self, Token },
 };
@@ -9,6 +22,12 @@ pub struct Parser {
     pub tree: Vec<Stmt>,
     cursor: usize,
     pub errors: Vec<CompilerError>,
+    /// Whether `Name {` begins a struct literal. It doesn't in the condition of an `if`
+    /// or `while`, where the `{` begins the body instead
+    struct_literals: bool,
+    /// Set after an error until the parser has skipped to the end of the statement that
+    /// caused it, any errors in between are most likely caused by the first one
+    panicking: bool,
 }
 
 impl Parser {
@@ -18,6 +37,8 @@ impl Parser {
             tree: vec![],
             cursor: 0usize,
             errors: vec![],
+            struct_literals: true,
+            panicking: false,
         }
     }
 
@@ -37,9 +58,111 @@ impl Parser {
         return self.stream.get(self.cursor + 1).unwrap_or(self.stream.last().unwrap());
     }
 
-    fn span(&self) -> (usize, usize, usize) {
+    fn span(&self) -> (usize, usize) {
         let t = self.current();
-        (t.line, t.offset, t.offset + t.lexeme.len())
+        (t.offset, t.offset + t.len)
+    }
+
+    /// Returns the span of the last token in the file, for errors about something missing
+    /// after it
+    fn last_span(&self) -> (usize, usize) {
+        return self.stream
+            .iter()
+            .rev()
+            .find(|t| !matches!(t.kind, token::Kind::Newline | token::Kind::End))
+            .map_or((0, 0), |t| (t.offset, t.offset + t.len));
+    }
+
+    /// Returns `span` extended to the end of the current token
+    fn extend(&self, span: Span) -> Span {
+        let (start, stop) = self.span();
+        return span.merge(&Span::new(start, stop));
+    }
+
+    /// Reports an error, unless the statement being parsed has already had one
+    fn error(&mut self, error: CompilerError) {
+        if !self.panicking {
+            self.errors.push(error);
+        }
+        self.panicking = true;
+    }
+
+    /// Skips the rest of a statement that had an error, leaving the cursor just before
+    /// the `Newline`, `Semicolon` or `RCurl` that ends it. Blocks opened along the way
+    /// are skipped entirely
+    fn synchronize(&mut self) {
+        self.panicking = false;
+
+        // The error may have been found at the end of the statement already. A `}` here
+        // closed a block of the statement, so what comes after it is still to be skipped
+        if
+            matches!(
+                self.current().kind,
+                token::Kind::Newline | token::Kind::Semicolon | token::Kind::End
+            )
+        {
+            self.cursor -= 1;
+            return;
+        }
+
+        let mut depth = 0usize;
+        loop {
+            match self.peek().kind {
+                token::Kind::End => {
+                    return;
+                }
+                token::Kind::Newline | token::Kind::Semicolon | token::Kind::RCurl if
+                    depth == 0
+                => {
+                    return;
+                }
+                token::Kind::LCurl => {
+                    depth += 1;
+                }
+                token::Kind::RCurl => {
+                    depth -= 1;
+                }
+                _ => {}
+            }
+            self.cursor += 1;
+        }
+    }
+
+    /// Advances onto the next token if it is of the given kind, otherwise reports
+    /// `message` at that token and leaves the cursor where it is
+    fn expect(&mut self, kind: token::Kind, message: &str) -> bool {
+        if self.peek().kind == kind {
+            self.cursor += 1;
+            return true;
+        }
+
+        let t = self.peek();
+        let (start, stop) = match t.kind {
+            token::Kind::End => self.last_span(),
+            _ => (t.offset, t.offset + t.len),
+        };
+        let len = stop - start;
+        self.error(
+            CompilerError::new(errors::Kind::SyntaxError, errors::Flag::Abort, start, len, message)
+        );
+        return false;
+    }
+
+    /// Parses the condition of an `if` or `while`
+    fn condition(&mut self) -> Expr {
+        let outer = std::mem::replace(&mut self.struct_literals, false);
+        let condition = self.assignment();
+        self.struct_literals = outer;
+        return condition;
+    }
+
+    /// Parses an expression inside of delimiters like `()` or `[]`, where a struct
+    /// literal is always allowed
+    fn nested(&mut self) -> Expr {
+        let outer = std::mem::replace(&mut self.struct_literals, true);
+        let expression = self.assignment();
+        self.struct_literals = outer;
+        return expression;
     }
 }
 
@@ -52,8 +175,8 @@ impl Parser {
         let mut args: Vec<Box<Expr>> = vec![];
 
         while self.current().kind != token::Kind::RParen {
-            let (line, start, stop) = self.span();
-            let expression = self.function_call(); // (NOTE) change this
+            let (start, stop) = self.span();
+            let expression = self.nested();
             // (TODO) check to make sure expression is valid here
 
             args.push(Box::new(expression));
@@ -65,11 +188,10 @@ impl Parser {
                 self.cursor += 1;
                 continue;
             } else {
-                self.errors.push(
+                self.error(
                     CompilerError::new(
                         errors::Kind::SyntaxError,
                         errors::Flag::Abort,
-                        line,
                         start,
                         stop - start,
                         "expected ',' for more arguments or ')' to close function call"
@@ -82,35 +204,106 @@ impl Parser {
         return args;
     }
 
-    /// Basic binary expression post-fixup:
-    /// If RHS is a binary expression and the operator of that expression is less than the original operator, then swap the operators and swap the original LHS with the LHS of the other expression
-    ///
-    /// `5 * 3 + 10` becomes `10 + 3 * 5`
-    fn binary_post_fixup(mut expression: Expr) -> Expr {
-        match expression {
-            Expr::Binary { span: _, ref mut lhs, ref mut rhs, ref mut op } => {
-                match **rhs {
-                    Expr::Binary { span: _, lhs: _, rhs: ref mut rhs_rhs, op: ref mut rhs_op } => {
-                        if rhs_op.precedence() < op.precedence() {
-                            // Swap the operators and nodes
-                            std::mem::swap(op, rhs_op);
-                            // Swap original LHS with RHS.LHS
-                            std::mem::swap(lhs, rhs_rhs);
-                        }
-                    }
-                    _ => {}
+    /// Returns the elements of an array literal, which may end with a trailing comma
+    /// START = `LBrac`
+    /// END = `RBrac`
+    fn array_elements(&mut self) -> Vec<Box<Expr>> {
+        let (start, stop) = self.span();
+        let mut elements: Vec<Box<Expr>> = vec![];
+
+        loop {
+            if self.peek_newline_insensitive().kind == token::Kind::RBrac {
+                self.cursor += 1;
+                break;
+            }
+
+            self.cursor += 1;
+            elements.push(Box::new(self.nested()));
+
+            match self.peek_newline_insensitive().kind {
+                token::Kind::Comma => {
+                    self.cursor += 1;
+                }
+                token::Kind::RBrac => {
+                    self.cursor += 1;
+                    break;
+                }
+                _ => {
+                    self.error(
+                        CompilerError::new(
+                            errors::Kind::SyntaxError,
+                            errors::Flag::Abort,
+                            start,
+                            stop - start,
+                            "expected ',' for more elements or ']' to close this array"
+                        )
+                    );
+                    break;
                 }
             }
-            _ => {}
         }
-        return expression;
+
+        return elements;
+    }
+
+    /// Returns the fields given to a struct literal
+    /// START = `LCurl`
+    /// END = `RCurl`
+    fn struct_literal_fields(&mut self) -> Vec<FieldInit> {
+        let (start, stop) = self.span();
+        let mut fields: Vec<FieldInit> = vec![];
+
+        loop {
+            if self.peek_newline_insensitive().kind == token::Kind::RCurl {
+                self.cursor += 1;
+                break;
+            }
+
+            if !self.expect(token::Kind::Symbol, "expected a field name") {
+                break;
+            }
+            let (field_start, field_stop) = self.span();
+            let span = Span::new(field_start, field_stop);
+            let name = self.current().lexeme.clone();
+
+            if !self.expect(token::Kind::Colon, "expected ':' and a value after this field name") {
+                break;
+            }
+            self.cursor += 1;
+            let value = self.nested();
+            fields.push(FieldInit { span, name, value: Box::new(value) });
+
+            match self.peek_newline_insensitive().kind {
+                token::Kind::Comma => {
+                    self.cursor += 1;
+                }
+                token::Kind::RCurl => {
+                    self.cursor += 1;
+                    break;
+                }
+                _ => {
+                    self.error(
+                        CompilerError::new(
+                            errors::Kind::SyntaxError,
+                            errors::Flag::Abort,
+                            start,
+                            stop - start,
+                            "expected ',' for more fields or '}' to close this struct literal"
+                        )
+                    );
+                    break;
+                }
+            }
+        }
+
+        return fields;
     }
 }
 
 impl Parser {
     fn primary(&mut self) -> Expr {
-        let (line, start, stop) = self.span();
-        let mut span = Span::new(line, start, stop);
+        let (start, stop) = self.span();
+        let mut span = Span::new(start, stop);
 
         let token = self.current();
         match token.kind {
@@ -124,15 +317,25 @@ impl Parser {
             }
             token::Kind::Symbol => {
                 let name: String = token.lexeme.clone();
+                if self.struct_literals && self.peek().kind == token::Kind::LCurl {
+                    self.cursor += 1;
+                    let fields = self.struct_literal_fields();
+                    return Expr::Struct { span: self.extend(span), name, fields };
+                }
                 return Expr::Symbol { span, name };
             }
+            token::Kind::True => {
+                return Expr::Bool { span, value: true };
+            }
+            token::Kind::False => {
+                return Expr::Bool { span, value: false };
+            }
             token::Kind::Integer => {
                 let value: i32 = token.lexeme.parse().unwrap_or_else(|_| {
-                    self.errors.push(
+                    self.error(
                         CompilerError::new(
                             errors::Kind::ParseError,
                             errors::Flag::Abort,
-                            line,
                             start,
                             stop - start,
                             "there was a compiler error parsing this integer literal."
@@ -144,11 +347,10 @@ impl Parser {
             }
             token::Kind::Float => {
                 let value: f64 = token.lexeme.parse().unwrap_or_else(|_| {
-                    self.errors.push(
+                    self.error(
                         CompilerError::new(
                             errors::Kind::ParseError,
                             errors::Flag::Abort,
-                            line,
                             start,
                             stop - start,
                             "there was a compiler error parsing this float literal."
@@ -159,30 +361,37 @@ impl Parser {
                 return Expr::Float { span, value };
             }
             token::Kind::End => {
-                self.errors.push(
+                let (last_start, last_stop) = self.last_span();
+                self.error(
                     CompilerError::new(
                         errors::Kind::SyntaxError,
                         errors::Flag::Abort,
-                        line,
-                        start - 1,
-                        1,
+                        last_start,
+                        last_stop - last_start,
                         "expected an expression after this but found EOF (end of file) instead."
                     )
                 );
                 span.valid = false;
                 return Expr::Empty { span };
             }
+            token::Kind::LBrac => {
+                let elements = self.array_elements();
+                return Expr::Array { span: self.extend(span), elements };
+            }
+            token::Kind::Match => {
+                return self.match_expression();
+            }
             token::Kind::LParen => {
                 self.cursor += 1;
-                let inner = self.assignment();
+                let inner = self.nested();
                 if self.peek_newline_insensitive().kind == token::Kind::RParen {
-                    return Expr::Grouping { span, inner: Box::new(inner) };
+                    self.cursor += 1; // consume RParen
+                    return Expr::Grouping { span: self.extend(span), inner: Box::new(inner) };
                 } else {
-                    self.errors.push(
+                    self.error(
                         CompilerError::new(
                             errors::Kind::SyntaxError,
                             errors::Flag::Abort,
-                            line,
                             start,
                             stop - start,
                             "mismatch parenthesis, expected ')' to close this grouping expression"
@@ -193,11 +402,10 @@ impl Parser {
                 return Expr::Empty { span };
             }
             _ => {
-                self.errors.push(
+                self.error(
                     CompilerError::new(
                         errors::Kind::SyntaxError,
                         errors::Flag::Abort,
-                        line,
                         start,
                         stop - start,
                         "expected an expression here."
@@ -209,73 +417,246 @@ impl Parser {
         }
     }
 
-    fn function_call(&mut self) -> Expr {
-        let mut expression = self.primary();
+    /// Parses a match expression, whose arms are separated by commas or newlines
+    /// START = `Match`
+    /// END = `RCurl`
+    fn match_expression(&mut self) -> Expr {
+        let (start, stop) = self.span();
+        let mut span = Span::new(start, stop);
+
+        self.cursor += 1;
+        let scrutinee = self.condition();
+        if !self.expect(token::Kind::LCurl, "expected '{' after the value being matched") {
+            span.valid = false;
+            return Expr::Empty { span };
+        }
 
-        while self.peek().kind == token::Kind::LParen {
-            let (line, start, stop) = self.span();
-            let span = Span::new(line, start, stop);
+        let mut arms: Vec<MatchArm> = vec![];
+        loop {
+            if self.peek_newline_insensitive().kind == token::Kind::RCurl {
+                self.cursor += 1;
+                break;
+            }
 
             self.cursor += 1;
-            let arguments = self.function_call_arguments();
-            expression = Expr::FunctionCall { span, callee: Box::new(expression), arguments };
+            let pattern = self.pattern();
+            if !self.expect(token::Kind::FatArrow, "expected '=>' after this pattern") {
+                break;
+            }
+            self.cursor += 1;
+            let body = self.nested();
+            arms.push(MatchArm { pattern, body: Box::new(body) });
+
+            match self.peek().kind {
+                token::Kind::Comma => {
+                    self.cursor += 1;
+                }
+                token::Kind::Newline | token::Kind::RCurl => {}
+                _ => {
+                    self.cursor += 1;
+                    let (start, stop) = self.span();
+                    self.error(
+                        CompilerError::new(
+                            errors::Kind::SyntaxError,
+                            errors::Flag::Abort,
+                            start,
+                            stop - start,
+                            "expected ',' or a newline for more arms or '}' to close this match"
+                        )
+                    );
+                    break;
+                }
+            }
         }
 
-        return expression;
+        return Expr::Match { span: self.extend(span), scrutinee: Box::new(scrutinee), arms };
     }
 
-    fn term(&mut self) -> Expr {
-        let mut expression = self.function_call();
+    /// START = first token of the pattern
+    /// END = last token of the pattern
+    fn pattern(&mut self) -> Pattern {
+        let (start, stop) = self.span();
+        let mut span = Span::new(start, stop);
 
-        while let Some(op) = AstOp::from_token(&self.peek().kind) {
-            if op.precedence() != 0 {
-                break;
+        let token = self.current().clone();
+        match token.kind {
+            token::Kind::Symbol if token.lexeme == "_" => Pattern::Wildcard { span },
+            token::Kind::Symbol => {
+                if self.peek().kind != token::Kind::LParen {
+                    return Pattern::Symbol { span, name: token.lexeme };
+                }
+
+                self.cursor += 1;
+                let mut fields: Vec<Pattern> = vec![];
+                loop {
+                    if self.peek().kind == token::Kind::RParen {
+                        self.cursor += 1;
+                        break;
+                    }
+
+                    self.cursor += 1;
+                    fields.push(self.pattern());
+                    if self.peek().kind == token::Kind::Comma {
+                        self.cursor += 1;
+                        continue;
+                    }
+                    self.expect(
+                        token::Kind::RParen,
+                        "expected ',' for more patterns or ')' to close this variant"
+                    );
+                    break;
+                }
+                return Pattern::Variant { span: self.extend(span), name: token.lexeme, fields };
+            }
+            token::Kind::Integer => {
+                let value: i32 = token.lexeme.parse().unwrap_or_else(|_| {
+                    self.error(
+                        CompilerError::new(
+                            errors::Kind::ParseError,
+                            errors::Flag::Abort,
+                            start,
+                            stop - start,
+                            "there was a compiler error parsing this integer literal."
+                        )
+                    );
+                    return 0i32;
+                });
+                return Pattern::Integer { span, value };
+            }
+            token::Kind::String => Pattern::String { span, value: token.lexeme },
+            token::Kind::True => Pattern::Bool { span, value: true },
+            token::Kind::False => Pattern::Bool { span, value: false },
+            token::Kind::End => {
+                let (last_start, last_stop) = self.last_span();
+                self.error(
+                    CompilerError::new(
+                        errors::Kind::SyntaxError,
+                        errors::Flag::Abort,
+                        last_start,
+                        last_stop - last_start,
+                        "expected a pattern after this but found EOF (end of file) instead."
+                    )
+                );
+                span.valid = false;
+                return Pattern::Empty { span };
+            }
+            _ => {
+                self.error(
+                    CompilerError::new(
+                        errors::Kind::SyntaxError,
+                        errors::Flag::Abort,
+                        start,
+                        stop - start,
+                        "expected a pattern here."
+                    )
+                );
+                span.valid = false;
+                return Pattern::Empty { span };
             }
+        }
+    }
 
-            self.cursor += 1;
-            let (line, start, stop) = self.span();
-            let span = Span::new(line, start, stop);
+    /// Parses any chain of function calls, indexing and field accesses after a primary
+    /// expression, e.g. `f(x)[0].y`
+    fn postfix(&mut self) -> Expr {
+        let mut expression = self.primary();
 
-            self.cursor += 1;
-            let rhs = self.factor();
+        loop {
+            match self.peek().kind {
+                token::Kind::LParen => {
+                    self.cursor += 1;
+                    let arguments = self.function_call_arguments();
+                    expression = Expr::FunctionCall {
+                        span: self.extend(expression.span()),
+                        callee: Box::new(expression),
+                        arguments,
+                    };
+                }
+                token::Kind::LBrac => {
+                    self.cursor += 2;
+                    let index = self.nested();
+                    self.expect(token::Kind::RBrac, "expected ']' to close this index");
+                    expression = Expr::Index {
+                        span: self.extend(expression.span()),
+                        target: Box::new(expression),
+                        index: Box::new(index),
+                    };
+                }
+                token::Kind::Dot => {
+                    self.cursor += 1;
+                    let (start, stop) = self.span();
+                    if !self.expect(token::Kind::Symbol, "expected a field name after '.'") {
+                        let mut span = Span::new(start, stop);
+                        span.valid = false;
+                        return Expr::Empty { span };
+                    }
 
-            expression = Expr::Binary { span, lhs: Box::new(expression), rhs: Box::new(rhs), op };
+                    let span = self.extend(expression.span());
+                    let name = self.current().lexeme.clone();
+                    expression = Expr::Field { span, target: Box::new(expression), name };
+                }
+                _ => {
+                    break;
+                }
+            }
         }
 
         return expression;
     }
 
-    fn factor(&mut self) -> Expr {
-        let mut expression = self.term();
+    /// Parses prefix operators, which bind tighter than any binary operator but
+    /// looser than function calls and indexing, so `-f(x)` negates the result of the call
+    fn unary(&mut self) -> Expr {
+        if let Some(op) = AstOp::unary_from_token(&self.current().kind) {
+            let (start, stop) = self.span();
+            let span = Span::new(start, stop);
+
+            self.cursor += 1;
+            let operand = self.unary();
+            let span = span.merge(&operand.span());
+            return Expr::Unary { span, op, operand: Box::new(operand) };
+        }
+
+        return self.postfix();
+    }
+
+    /// Parses a chain of binary operators by precedence climbing. Only operators that
+    /// bind at least as tightly as `min_precedence` are consumed here, anything looser
+    /// is left for the caller
+    fn binary(&mut self, min_precedence: i8) -> Expr {
+        let mut expression = self.unary();
 
         while let Some(op) = AstOp::from_token(&self.peek().kind) {
-            if op.precedence() != 1 {
+            let precedence = op.precedence();
+            if precedence < min_precedence || op == AstOp::Reassign {
                 break;
             }
 
             self.cursor += 1;
-            let (line, start, stop) = self.span();
-            let span = Span::new(line, start, stop);
+
+            // Left associative operators require the RHS to bind strictly tighter
+            let next_precedence = match op.associativity() {
+                Associativity::Left => precedence + 1,
+                Associativity::Right => precedence,
+            };
 
             self.cursor += 1;
-            let rhs = self.factor();
+            let rhs = self.binary(next_precedence);
 
+            let span = expression.span().merge(&rhs.span());
             expression = Expr::Binary { span, lhs: Box::new(expression), rhs: Box::new(rhs), op };
-            expression = Self::binary_post_fixup(expression);
         }
 
         return expression;
     }
 
     fn assignment(&mut self) -> Expr {
-        let expression = self.factor();
-
-        while self.peek().kind == token::Kind::Arrow {
-            let (line, start, stop) = self.span();
-            let span = Span::new(line, start, stop);
+        let expression = self.binary(0);
 
+        if self.peek().kind == token::Kind::Arrow {
             self.cursor += 2;
             let value = self.assignment();
+            let span = expression.span().merge(&value.span());
             return Expr::Assignment { span, lhs: Box::new(expression), rhs: Box::new(value) };
         }
 
@@ -286,57 +667,452 @@ impl Parser {
 impl Parser {
     pub fn parse(&mut self) {
         'statements: loop {
-            if self.current().kind == token::Kind::End {
-                break 'statements;
+            let (start, stop) = self.span();
+            match self.current().kind {
+                token::Kind::End => break 'statements,
+                token::Kind::Newline | token::Kind::Semicolon => {
+                    self.cursor += 1;
+                    continue 'statements;
+                }
+                token::Kind::RCurl => {
+                    self.error(
+                        CompilerError::new(
+                            errors::Kind::SyntaxError,
+                            errors::Flag::Abort,
+                            start,
+                            stop - start,
+                            "this '}' doesn't close any block"
+                        )
+                    );
+                    self.panicking = false;
+                    self.cursor += 1;
+                    continue 'statements;
+                }
+                _ => {}
             }
 
             let statement = self.statement();
             self.tree.push(statement);
+            if self.panicking {
+                self.synchronize();
+            }
             self.cursor += 1;
         }
     }
 
-    fn binding(&mut self, token: Token, mutable: bool) -> Stmt {
-        self.cursor += 1; // consume EQUAL
-        let (line, start, stop) = self.span();
-        let span = Span::new(line, start, stop);
+    /// START = first token of the type
+    /// END = last token of the type
+    fn type_expr(&mut self) -> TypeExpr {
+        let (start, stop) = self.span();
+        let mut span = Span::new(start, stop);
+
+        match self.current().kind {
+            token::Kind::Symbol => {
+                let name = self.current().lexeme.clone();
+                return TypeExpr::Named { span, name };
+            }
+            token::Kind::LBrac => {
+                self.cursor += 1;
+                let element = self.type_expr();
+                if !self.expect(token::Kind::RBrac, "expected ']' to close this array type") {
+                    span.valid = false;
+                    return TypeExpr::Empty { span };
+                }
+                return TypeExpr::Array { span: self.extend(span), element: Box::new(element) };
+            }
+            token::Kind::End => {
+                let (last_start, last_stop) = self.last_span();
+                self.error(
+                    CompilerError::new(
+                        errors::Kind::SyntaxError,
+                        errors::Flag::Abort,
+                        last_start,
+                        last_stop - last_start,
+                        "expected a type after this but found EOF (end of file) instead."
+                    )
+                );
+                span.valid = false;
+                return TypeExpr::Empty { span };
+            }
+            _ => {
+                self.error(
+                    CompilerError::new(
+                        errors::Kind::SyntaxError,
+                        errors::Flag::Abort,
+                        start,
+                        stop - start,
+                        "expected a type here."
+                    )
+                );
+                span.valid = false;
+                return TypeExpr::Empty { span };
+            }
+        }
+    }
+
+    /// Parses a binding, where `span` is that of its first token, either `Mut` or the name
+    /// START = `Symbol` being bound
+    /// END = last token of the initializer
+    fn binding(&mut self, span: Span, token: Token, mutable: bool) -> Stmt {
+        let (start, stop) = self.span();
+        let name_span = Span::new(start, stop);
+
+        let mut annotation: Option<TypeExpr> = None;
+        if self.peek().kind == token::Kind::Colon {
+            self.cursor += 2; // consume COLON
+            annotation = Some(self.type_expr());
+        }
+
+        if !self.expect(token::Kind::Equal, "expected '=' to give this binding a value") {
+            return Stmt::Empty { span };
+        }
 
         self.cursor += 1;
         let value = self.assignment();
         return Stmt::Binding {
-            span,
+            span: span.merge(&value.span()),
             mutable,
             name: token.lexeme,
+            name_span,
             initializer: Some(Box::new(value)),
-            annotation: None,
+            annotation,
         };
     }
 
+    /// Parses the statements of a block
+    /// START = `LCurl`
+    /// END = `RCurl`
+    fn block(&mut self) -> Vec<Stmt> {
+        let (start, stop) = self.span();
+        let mut statements: Vec<Stmt> = vec![];
+
+        'statements: loop {
+            self.cursor += 1;
+            match self.current().kind {
+                token::Kind::Newline | token::Kind::Semicolon => continue 'statements,
+                token::Kind::RCurl => break 'statements,
+                token::Kind::End => {
+                    self.error(
+                        CompilerError::new(
+                            errors::Kind::SyntaxError,
+                            errors::Flag::Abort,
+                            start,
+                            stop - start,
+                            "this block has no closing '}'"
+                        )
+                    );
+                    break 'statements;
+                }
+                _ => {
+                    statements.push(self.statement());
+                    if self.panicking {
+                        self.synchronize();
+                    }
+                }
+            }
+        }
+
+        return statements;
+    }
+
+    /// Parses the parameter names of a function declaration
+    /// START = `LParen`
+    /// END = `RParen`
+    fn parameters(&mut self) -> Vec<Parameter> {
+        let mut parameters: Vec<Parameter> = vec![];
+        if self.peek().kind == token::Kind::RParen {
+            self.cursor += 1;
+            return parameters;
+        }
+
+        loop {
+            if !self.expect(token::Kind::Symbol, "expected a parameter name") {
+                break;
+            }
+
+            let (start, stop) = self.span();
+            let name = self.current().lexeme.clone();
+            parameters.push(Parameter { span: Span::new(start, stop), name });
+
+            if self.peek().kind == token::Kind::Comma {
+                self.cursor += 1;
+                continue;
+            }
+            self.expect(
+                token::Kind::RParen,
+                "expected ',' for more parameters or ')' to close the parameter list"
+            );
+            break;
+        }
+
+        return parameters;
+    }
+
+    /// Parses a function declaration
+    /// START = `Function`
+    /// END = `RCurl`
+    fn function(&mut self) -> Stmt {
+        let (start, stop) = self.span();
+        let span = Span::new(start, stop);
+
+        if !self.expect(token::Kind::Symbol, "expected a name after 'function'") {
+            return Stmt::Empty { span };
+        }
+        let name = self.current().lexeme.clone();
+        let (start, stop) = self.span();
+        let name_span = Span::new(start, stop);
+
+        if !self.expect(token::Kind::LParen, "expected '(' to begin the parameter list") {
+            return Stmt::Empty { span };
+        }
+        let parameters = self.parameters();
+
+        if !self.expect(token::Kind::LCurl, "expected '{' to begin the function body") {
+            return Stmt::Empty { span };
+        }
+        let body = self.block();
+
+        return Stmt::Function { span: self.extend(span), name, name_span, parameters, body };
+    }
+
+    /// Parses a struct declaration, whose fields are separated by commas or newlines
+    /// START = `Struct`
+    /// END = `RCurl`
+    fn struct_declaration(&mut self) -> Stmt {
+        let (start, stop) = self.span();
+        let span = Span::new(start, stop);
+
+        if !self.expect(token::Kind::Symbol, "expected a name after 'struct'") {
+            return Stmt::Empty { span };
+        }
+        let name = self.current().lexeme.clone();
+
+        if !self.expect(token::Kind::LCurl, "expected '{' to begin the fields of this struct") {
+            return Stmt::Empty { span };
+        }
+
+        let mut fields: Vec<Field> = vec![];
+        loop {
+            if self.peek_newline_insensitive().kind == token::Kind::RCurl {
+                self.cursor += 1;
+                break;
+            }
+
+            if !self.expect(token::Kind::Symbol, "expected a field name") {
+                break;
+            }
+            let (start, stop) = self.span();
+            let field_span = Span::new(start, stop);
+            let field_name = self.current().lexeme.clone();
+
+            if !self.expect(token::Kind::Colon, "expected ':' and a type after this field name") {
+                break;
+            }
+            self.cursor += 1;
+            let annotation = self.type_expr();
+            fields.push(Field { span: field_span, name: field_name, annotation });
+
+            match self.peek().kind {
+                token::Kind::Comma => {
+                    self.cursor += 1;
+                }
+                token::Kind::Newline | token::Kind::RCurl => {}
+                _ => {
+                    self.cursor += 1;
+                    let (start, stop) = self.span();
+                    self.error(
+                        CompilerError::new(
+                            errors::Kind::SyntaxError,
+                            errors::Flag::Abort,
+                            start,
+                            stop - start,
+                            "expected ',' or a newline for more fields or '}' to close this struct"
+                        )
+                    );
+                    break;
+                }
+            }
+        }
+
+        return Stmt::Struct { span: self.extend(span), name, fields };
+    }
+
+    /// Parses an enum declaration, whose variants are separated by commas or newlines
+    /// START = `Enum`
+    /// END = `RCurl`
+    fn enum_declaration(&mut self) -> Stmt {
+        let (start, stop) = self.span();
+        let span = Span::new(start, stop);
+
+        if !self.expect(token::Kind::Symbol, "expected a name after 'enum'") {
+            return Stmt::Empty { span };
+        }
+        let name = self.current().lexeme.clone();
+
+        if !self.expect(token::Kind::LCurl, "expected '{' to begin the variants of this enum") {
+            return Stmt::Empty { span };
+        }
+
+        let mut variants: Vec<Variant> = vec![];
+        loop {
+            if self.peek_newline_insensitive().kind == token::Kind::RCurl {
+                self.cursor += 1;
+                break;
+            }
+
+            if !self.expect(token::Kind::Symbol, "expected a variant name") {
+                break;
+            }
+            let (start, stop) = self.span();
+            let variant_span = Span::new(start, stop);
+            let variant_name = self.current().lexeme.clone();
+
+            let mut payload: Vec<TypeExpr> = vec![];
+            if self.peek().kind == token::Kind::LParen {
+                self.cursor += 1;
+                loop {
+                    self.cursor += 1;
+                    payload.push(self.type_expr());
+                    if self.peek().kind == token::Kind::Comma {
+                        self.cursor += 1;
+                        continue;
+                    }
+                    self.expect(
+                        token::Kind::RParen,
+                        "expected ',' for more types or ')' to close this payload"
+                    );
+                    break;
+                }
+            }
+            variants.push(Variant { span: variant_span, name: variant_name, payload });
+
+            match self.peek().kind {
+                token::Kind::Comma => {
+                    self.cursor += 1;
+                }
+                token::Kind::Newline | token::Kind::RCurl => {}
+                _ => {
+                    self.cursor += 1;
+                    let (start, stop) = self.span();
+                    self.error(
+                        CompilerError::new(
+                            errors::Kind::SyntaxError,
+                            errors::Flag::Abort,
+                            start,
+                            stop - start,
+                            "expected ',' or a newline for more variants or '}' to close this enum"
+                        )
+                    );
+                    break;
+                }
+            }
+        }
+
+        return Stmt::Enum { span: self.extend(span), name, variants };
+    }
+
+    /// Parses an if statement along with any chain of `else if` and `else` branches
+    /// START = `If`
+    /// END = `RCurl` of the last branch
+    fn if_statement(&mut self) -> Stmt {
+        let (start, stop) = self.span();
+        let span = Span::new(start, stop);
+
+        self.cursor += 1;
+        let condition = self.condition();
+
+        if !self.expect(token::Kind::LCurl, "expected '{' after the condition of this 'if'") {
+            return Stmt::Empty { span };
+        }
+        let body = self.block();
+
+        let mut else_branch: Option<Box<Stmt>> = None;
+        if self.peek().kind == token::Kind::Else {
+            self.cursor += 1;
+            let (start, stop) = self.span();
+            let else_span = Span::new(start, stop);
+
+            if self.peek().kind == token::Kind::If {
+                self.cursor += 1;
+                else_branch = Some(Box::new(self.if_statement()));
+            } else if self.expect(token::Kind::LCurl, "expected '{' or 'if' after 'else'") {
+                let body = self.block();
+                let span = self.extend(else_span);
+                else_branch = Some(Box::new(Stmt::Block { span, body }));
+            }
+        }
+
+        let span = self.extend(span);
+        return Stmt::If { span, condition: Box::new(condition), body, else_branch };
+    }
+
+    /// START = `While`
+    /// END = `RCurl`
+    fn while_statement(&mut self) -> Stmt {
+        let (start, stop) = self.span();
+        let span = Span::new(start, stop);
+
+        self.cursor += 1;
+        let condition = self.condition();
+
+        if !self.expect(token::Kind::LCurl, "expected '{' after the condition of this 'while'") {
+            return Stmt::Empty { span };
+        }
+        let body = self.block();
+
+        return Stmt::While { span: self.extend(span), condition: Box::new(condition), body };
+    }
+
+    /// START = `Return`
+    /// END = last token of the returned value, or `Return` if there is none
+    fn return_statement(&mut self) -> Stmt {
+        let (start, stop) = self.span();
+        let span = Span::new(start, stop);
+
+        match self.peek().kind {
+            | token::Kind::Newline
+            | token::Kind::Semicolon
+            | token::Kind::RCurl
+            | token::Kind::End => {
+                return Stmt::Return { span, value: None };
+            }
+            _ => {
+                self.cursor += 1;
+                let value = self.assignment();
+                let span = span.merge(&value.span());
+                return Stmt::Return { span, value: Some(Box::new(value)) };
+            }
+        }
+    }
+
     fn end_statement(&mut self, statement: Stmt) -> Stmt {
         match self.peek().kind {
-            token::Kind::Newline | token::Kind::Semicolon | token::Kind::End => statement,
+            | token::Kind::Newline
+            | token::Kind::Semicolon
+            | token::Kind::RCurl
+            | token::Kind::End => statement,
             _ => {
-                let (line, start, stop) = self.span();
-                let span = Span::new(line, start, stop);
-                self.errors.push(
+                // What follows the statement is what shouldn't be there
+                let t = self.peek();
+                let (start, stop) = (t.offset, t.offset + t.len);
+                self.error(
                     CompilerError::new(
                         errors::Kind::SyntaxError,
                         errors::Flag::Abort,
-                        line,
                         start,
                         stop - start,
                         "expected newline or ';' after statement"
                     )
                 );
-                return Stmt::Empty { span };
+                return Stmt::Empty { span: statement.span() };
             }
         }
     }
 
     fn statement(&mut self) -> Stmt {
         let token: Token = self.current().clone();
-        let (line, start, stop) = self.span();
-        let span = Span::new(line, start, stop);
+        let (start, stop) = self.span();
+        let span = Span::new(start, stop);
 
         match token.kind {
             token::Kind::Newline => {
@@ -344,22 +1120,58 @@ impl Parser {
                 return self.statement();
             }
             token::Kind::Symbol => {
-                if self.peek().kind == token::Kind::Equal {
-                    let stmt = self.binding(token, false);
+                if matches!(self.peek().kind, token::Kind::Equal | token::Kind::Colon) {
+                    let stmt = self.binding(span, token, false);
                     return self.end_statement(stmt);
                 }
                 // Anything other than the above will fall through to the default case
             }
+            token::Kind::LCurl => {
+                let body = self.block();
+                let stmt = Stmt::Block { span: self.extend(span), body };
+                return self.end_statement(stmt);
+            }
+            token::Kind::If => {
+                let stmt = self.if_statement();
+                return self.end_statement(stmt);
+            }
+            token::Kind::While => {
+                let stmt = self.while_statement();
+                return self.end_statement(stmt);
+            }
+            token::Kind::Break => {
+                let stmt = Stmt::Break { span };
+                return self.end_statement(stmt);
+            }
+            token::Kind::Continue => {
+                let stmt = Stmt::Continue { span };
+                return self.end_statement(stmt);
+            }
+            token::Kind::Function => {
+                let stmt = self.function();
+                return self.end_statement(stmt);
+            }
+            token::Kind::Struct => {
+                let stmt = self.struct_declaration();
+                return self.end_statement(stmt);
+            }
+            token::Kind::Enum => {
+                let stmt = self.enum_declaration();
+                return self.end_statement(stmt);
+            }
+            token::Kind::Return => {
+                let stmt = self.return_statement();
+                return self.end_statement(stmt);
+            }
             token::Kind::Mut => {
                 let symbol = self.peek();
                 if symbol.kind != token::Kind::Symbol {
                     self.cursor += 1;
-                    let (line, start, stop) = self.span();
-                    self.errors.push(
+                    let (start, stop) = self.span();
+                    self.error(
                         CompilerError::new(
                             errors::Kind::SyntaxError,
                             errors::Flag::Abort,
-                            line,
                             start,
                             stop - start,
                             "expected a symbol after 'mut'"
@@ -368,8 +1180,9 @@ impl Parser {
                     return Stmt::Empty { span };
                 }
                 self.cursor += 1; // consume SYMBOL
-                if self.peek().kind == token::Kind::Equal {
-                    let stmt = self.binding(token, true);
+                if matches!(self.peek().kind, token::Kind::Equal | token::Kind::Colon) {
+                    let symbol = self.current().clone();
+                    let stmt = self.binding(span, symbol, true);
                     return self.end_statement(stmt);
                 }
             }
@@ -382,18 +1195,18 @@ impl Parser {
                 let stmt = Stmt::Expression { span, expr: expression };
                 return self.end_statement(stmt);
             }
-            Expr::FunctionCall { span, callee: _, arguments: _ } => {
+            | Expr::FunctionCall { span, callee: _, arguments: _ }
+            | Expr::Match { span, scrutinee: _, arms: _ } => {
                 let stmt = Stmt::Expression { span, expr: expression };
                 return self.end_statement(stmt);
             }
             _ => {}
         }
 
-        self.errors.push(
+        self.error(
             CompilerError::new(
                 errors::Kind::SyntaxError,
                 errors::Flag::Abort,
-                line,
                 start,
                 stop - start,
                 "expected a declaration, assignment, or function call"
@@ -402,3 +1215,109 @@ impl Parser {
         return Stmt::Empty { span };
     }
 }
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::lexer::Lexer;
+
+    /// Lexes and parses `source`, which has to be free of errors
+    pub fn parse(source: &str) -> Vec<Stmt> {
+        let mut lexer = Lexer::new(&source.to_string());
+        lexer.scan();
+        assert!(lexer.errors.is_empty(), "{} has lexer errors", source);
+        let mut parser = Parser::new(std::mem::take(&mut lexer.output));
+        parser.parse();
+        assert!(parser.errors.is_empty(), "{} has parser errors", source);
+        return parser.tree;
+    }
+
+    /// Lexes and parses `source`, returning the messages of the errors the parser reports
+    fn errors(source: &str) -> Vec<String> {
+        let mut lexer = Lexer::new(&source.to_string());
+        lexer.scan();
+        let mut parser = Parser::new(std::mem::take(&mut lexer.output));
+        parser.parse();
+        return parser.errors.iter().map(|error| error.message().to_string()).collect();
+    }
+
+    /// Lexes and parses `source`, returning the offset and length of each error
+    fn error_locations(source: &str) -> Vec<(usize, usize)> {
+        let mut lexer = Lexer::new(&source.to_string());
+        lexer.scan();
+        let mut parser = Parser::new(std::mem::take(&mut lexer.output));
+        parser.parse();
+        return parser.errors.iter().map(|error| error.location()).collect();
+    }
+
+    #[test]
+    fn recovery_after_a_block() {
+        let expected = ["expected newline or ';' after statement"];
+        assert_eq!(errors("function f() {\n} extra\ng = 1\n"), expected);
+        assert_eq!(errors("while a {\n    if a {\n    } junk\n    b = 1\n}\nc = 2\n"), expected);
+        assert_eq!(errors("x = 1 2\ny = 3\n"), expected);
+    }
+
+    #[test]
+    fn array_type_spans() {
+        let tree = parse("x: [[int]] = a");
+        let Stmt::Binding {
+            span: _,
+            mutable: _,
+            name: _,
+            name_span: _,
+            initializer: _,
+            annotation: Some(TypeExpr::Array { span, element }),
+        } = &tree[0] else {
+            panic!("{:?} has no array annotation", tree[0]);
+        };
+        assert_eq!((span.start, span.stop), (3, 10));
+        assert_eq!((element.span().start, element.span().stop), (4, 9));
+    }
+
+    #[test]
+    fn errors_at_the_end_of_the_file() {
+        // They point at the last token rather than past the end of the file
+        assert_eq!(error_locations("m = match a {\n    V("), [(19, 1)]);
+        assert_eq!(error_locations("x: ["), [(3, 1)]);
+    }
+
+    /// Returns the initializer of the single binding in `source` as an s-expression, e.g.
+    /// `(Plus 1 2)`, so that the shape of the tree is all that's compared
+    fn initializer(source: &str) -> String {
+        fn sexpr(expr: &Expr) -> String {
+            match expr {
+                Expr::Integer { span: _, value } => value.to_string(),
+                Expr::Binary { span: _, lhs, rhs, op } => {
+                    format!("({:?} {} {})", op, sexpr(lhs), sexpr(rhs))
+                }
+                _ => panic!("{:?} is not an integer or binary expression", expr),
+            }
+        }
+
+        let tree = parse(source);
+        let Stmt::Binding {
+            span: _,
+            mutable: _,
+            name: _,
+            name_span: _,
+            initializer: Some(initializer),
+            annotation: _,
+        } = &tree[0] else {
+            panic!("{:?} is not a binding", tree[0]);
+        };
+        return sexpr(initializer);
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(initializer("x = 5 * 3 + 10"), "(Plus (Multiply 5 3) 10)");
+        assert_eq!(initializer("x = 10 + 5 * 3"), "(Plus 10 (Multiply 5 3))");
+    }
+
+    #[test]
+    fn left_associativity() {
+        assert_eq!(initializer("x = 10 - 3 - 2"), "(Minus (Minus 10 3) 2)");
+        assert_eq!(initializer("x = 8 / 4 % 3"), "(Modulus (Divide 8 4) 3)");
+    }
+}
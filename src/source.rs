@@ -0,0 +1,87 @@
+/// A source file along with where each of its lines start. Positions everywhere else, in
+/// tokens, spans and errors, are byte offsets into `text`, and this turns them into the
+/// line and column a person would count
+pub struct Source {
+    pub path: String,
+    pub text: String,
+    /// The byte offset that each line starts at
+    lines: Vec<usize>,
+}
+
+impl Source {
+    pub fn new(path: String, text: String) -> Self {
+        let mut lines = vec![0usize];
+        lines.extend(text.match_indices('\n').map(|(offset, _)| offset + 1));
+        Self { path, text, lines }
+    }
+
+    /// Returns the line containing `offset`, counting from 1
+    pub fn line(&self, offset: usize) -> usize {
+        return self.lines.partition_point(|start| *start <= offset);
+    }
+
+    /// Returns the start and end (exclusive) of the line containing `offset`, not
+    /// including its line break
+    pub fn line_bounds(&self, offset: usize) -> (usize, usize) {
+        let line = self.line(offset);
+        let start = self.lines[line - 1];
+        let end = self.lines.get(line).map_or(self.text.len(), |next| next - 1);
+        return (start, self.text[start..end].trim_end_matches('\r').len() + start);
+    }
+
+    /// Returns the column of `offset` counting from 1, in characters rather than bytes
+    pub fn column(&self, offset: usize) -> usize {
+        let (start, _) = self.line_bounds(offset);
+        return self.width(start, offset) + 1;
+    }
+
+    /// Returns how many characters are between two offsets
+    pub fn width(&self, start: usize, stop: usize) -> usize {
+        let stop = stop.min(self.text.len());
+        return self.text[start.min(stop)..stop].chars().count();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(text: &str) -> Source {
+        return Source::new("test.chao".to_string(), text.to_string());
+    }
+
+    #[test]
+    fn columns_count_characters() {
+        // 'é' is two bytes and '→' three, but each is a single column
+        let source = file("s = \"é→\" + t\nnext");
+        let t = source.text.find('t').unwrap();
+        assert_eq!(t, 14);
+        assert_eq!((source.line(t), source.column(t)), (1, 12));
+        assert_eq!(source.width(4, 11), 4);
+        assert_eq!(source.line_bounds(t), (0, 15));
+        assert_eq!((source.line(16), source.column(16)), (2, 1));
+    }
+
+    #[test]
+    fn crlf_line_endings() {
+        let source = file("ab\r\ncd\r\n");
+        assert_eq!(source.line_bounds(0), (0, 2));
+        assert_eq!(source.line_bounds(5), (4, 6));
+        assert_eq!((source.line(4), source.column(4)), (2, 1));
+        assert_eq!((source.line(5), source.column(5)), (2, 2));
+    }
+
+    #[test]
+    fn offsets_at_the_end_of_the_file() {
+        // The end of a file with a trailing newline is on an empty last line
+        let source = file("ab\ncd\n");
+        assert_eq!((source.line(6), source.column(6)), (3, 1));
+        assert_eq!(source.line_bounds(6), (6, 6));
+
+        // Without one it is just after the last character
+        let source = file("ab\ncd");
+        assert_eq!((source.line(5), source.column(5)), (2, 3));
+        assert_eq!(source.line_bounds(5), (3, 5));
+        assert_eq!(source.line_bounds(3), (3, 5));
+    }
+}
@@ -14,7 +14,10 @@ pub enum Kind {
     Modulo,
 
     Arrow,
+    FatArrow,
     Comma,
+    Colon,
+    Dot,
 
     Equal,
     EqualEqual,
@@ -33,6 +36,17 @@ pub enum Kind {
     // Keywords
     Mut,
     Function,
+    Return,
+    If,
+    Else,
+    While,
+    Break,
+    Continue,
+    Struct,
+    Enum,
+    Match,
+    True,
+    False,
 
     // Miscellaneous
     Semicolon,
@@ -45,6 +59,17 @@ impl Kind {
         match string.as_str() {
             "mut" => Some(Kind::Mut),
             "function" => Some(Kind::Function),
+            "return" => Some(Kind::Return),
+            "if" => Some(Kind::If),
+            "else" => Some(Kind::Else),
+            "while" => Some(Kind::While),
+            "break" => Some(Kind::Break),
+            "continue" => Some(Kind::Continue),
+            "struct" => Some(Kind::Struct),
+            "enum" => Some(Kind::Enum),
+            "match" => Some(Kind::Match),
+            "true" => Some(Kind::True),
+            "false" => Some(Kind::False),
             _ => None,
         }
     }
@@ -53,7 +78,26 @@ impl Kind {
 #[derive(Debug, Clone)]
 pub struct Token {
     pub kind: Kind,
+    /// Byte offset of the start of the token in the source
     pub offset: usize,
-    pub line: usize,
+    /// Length of the token in bytes, which can differ from the lexeme for strings
+    pub len: usize,
     pub lexeme: String,
+    /// The whitespace and comments before this token, only kept if the lexer was asked to
+    pub trivia: Vec<Trivia>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    LineComment,
+    BlockComment,
+}
+
+/// Source text that has no meaning to the parser, but which a formatter has to keep
+#[derive(Debug, Clone)]
+pub struct Trivia {
+    pub kind: TriviaKind,
+    pub offset: usize,
+    pub text: String,
 }
@@ -0,0 +1,665 @@
+use crate::{
+    ast::{ Associativity, AstOp, Expr, Pattern, Stmt, TypeExpr, Visitor },
+    formatter::Layout,
+};
+
+const INDENT: &'static str = "    ";
+
+/// Turns an AST back into Chao source. The output is canonical rather than a copy of the
+/// source, so spacing is not kept, and parentheses are only printed where the tree could not
+/// be parsed back without them, whether or not the source had them. Comments and blank lines
+/// are only kept when given the `Layout` of the source
+pub struct Unparser {
+    pub output: String,
+    indent: usize,
+    /// Whether a struct literal can be printed without parentheses here, mirroring the
+    /// parser's restriction in the condition of an `if`, `while` or `match`
+    struct_literals: bool,
+    layout: Option<Layout>,
+    /// The end of the innermost block, match, struct or enum being printed. Comments after
+    /// it belong to whatever comes after the `}`
+    bound: usize,
+}
+
+impl Unparser {
+    pub fn new() -> Self {
+        Self {
+            output: String::new(),
+            indent: 0usize,
+            struct_literals: true,
+            layout: None,
+            bound: usize::MAX,
+        }
+    }
+
+    /// Keeps the comments and blank lines of the source the AST was parsed from
+    pub fn with_layout(mut self, layout: Layout) -> Self {
+        self.layout = Some(layout);
+        return self;
+    }
+
+    /// Starts a new line at the current indentation
+    fn newline(&mut self) {
+        self.output.push('\n');
+        self.output.push_str(&INDENT.repeat(self.indent));
+    }
+
+    /// Starts a new line unless nothing has been printed yet, leaving an empty line before
+    /// it if `blank` is set and it isn't the first line of a block
+    fn line(&mut self, blank: bool) {
+        if self.output.is_empty() {
+            return;
+        }
+        if blank && !self.output.ends_with('{') {
+            self.output.push('\n');
+        }
+        self.newline();
+    }
+
+    /// Starts the line of a statement, match arm, field or variant that begins at `start`,
+    /// after any comments that come before it
+    fn item(&mut self, start: usize) {
+        self.closing(start);
+        let blank = self.layout.as_mut().is_some_and(|layout| layout.begin(start));
+        self.line(blank);
+    }
+
+    /// Prints any comments on the same line as `end`, where the last item ended
+    fn trailing(&mut self, end: usize) {
+        let bound = self.bound;
+        let comments = self.layout.as_mut().map_or(vec![], |layout| layout.trailing(end, bound));
+        for comment in comments {
+            self.output.push(' ');
+            self.output.push_str(&comment);
+        }
+    }
+
+    /// Prints the comments before `end` on their own lines, such as those at the end of a
+    /// block before its closing `}`
+    fn closing(&mut self, end: usize) {
+        let comments = self.layout.as_mut().map_or(vec![], |layout| layout.comments_before(end));
+        for (blank, comment) in comments {
+            self.line(blank);
+            self.output.push_str(&comment);
+        }
+    }
+
+    /// Returns true if a block, match, struct or enum ending at `end` has nothing to
+    /// print, so it can be written as `{}`
+    fn is_empty(&self, items: usize, end: usize) -> bool {
+        return items == 0 && !self.layout.as_ref().is_some_and(|l| l.has_comment_before(end));
+    }
+
+    /// Begins printing the items of a block, match, struct or enum ending at `end`,
+    /// returning the bound to restore once they are done
+    fn open(&mut self, end: usize) -> usize {
+        self.output.push('{');
+        self.indent += 1;
+        return std::mem::replace(&mut self.bound, end);
+    }
+
+    /// Prints the comments left before `end` and the closing `}` of what `open` began
+    fn close(&mut self, end: usize, bound: usize) {
+        self.closing(end);
+        self.bound = bound;
+        self.indent -= 1;
+        self.newline();
+        self.output.push('}');
+    }
+
+    /// Returns how tightly an expression binds to its neighbours, anything other than an
+    /// operator binds tighter than all of them. A grouping is as tight as what it groups, as
+    /// its parentheses are left for the printer to decide
+    fn precedence(expr: &Expr) -> i8 {
+        match expr {
+            Expr::Grouping { span: _, inner } => Self::precedence(inner),
+            Expr::Assignment { span: _, lhs: _, rhs: _ } => AstOp::Reassign.precedence(),
+            Expr::Binary { span: _, lhs: _, rhs: _, op } => op.precedence(),
+            Expr::Unary { span: _, op, operand: _ } => op.precedence(),
+            _ => i8::MAX,
+        }
+    }
+
+    /// Prints `expr`, wrapping it in parentheses if `parenthesize` is set
+    fn operand(&mut self, expr: &Expr, parenthesize: bool) {
+        if parenthesize {
+            self.output.push('(');
+            self.nested(expr);
+            self.output.push(')');
+        } else {
+            self.visit_expr(expr);
+        }
+    }
+
+    /// Prints an expression inside of delimiters, where a struct literal is always allowed
+    fn nested(&mut self, expr: &Expr) {
+        let outer = std::mem::replace(&mut self.struct_literals, true);
+        self.visit_expr(expr);
+        self.struct_literals = outer;
+    }
+
+    /// Prints the condition of an `if` or `while`, or the value being matched
+    fn condition(&mut self, expr: &Expr) {
+        let outer = std::mem::replace(&mut self.struct_literals, false);
+        self.visit_expr(expr);
+        self.struct_literals = outer;
+    }
+
+    /// Prints a comma separated list of expressions
+    fn list(&mut self, exprs: &Vec<Box<Expr>>) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i > 0 {
+                self.output.push_str(", ");
+            }
+            self.nested(expr);
+        }
+    }
+
+    /// Returns a string literal that decodes back into `value`
+    fn string(value: &str) -> String {
+        let mut literal = String::from("\"");
+        for ch in value.chars() {
+            match ch {
+                '"' => literal.push_str("\\\""),
+                '\\' => literal.push_str("\\\\"),
+                '\n' => literal.push_str("\\n"),
+                '\t' => literal.push_str("\\t"),
+                '\r' => literal.push_str("\\r"),
+                '\0' => literal.push_str("\\0"),
+                ch if ch.is_control() => literal.push_str(&format!("\\u{{{:X}}}", ch as u32)),
+                ch => literal.push(ch),
+            }
+        }
+        literal.push('"');
+        return literal;
+    }
+
+    /// Returns a float literal that lexes as a float, even for whole numbers
+    fn float(value: f64) -> String {
+        let literal = value.to_string();
+        if literal.contains('.') {
+            return literal;
+        }
+        return format!("{}.0", literal);
+    }
+
+    fn operator(op: &AstOp) -> &'static str {
+        match op {
+            AstOp::Plus => "+",
+            AstOp::Minus => "-",
+            AstOp::Multiply => "*",
+            AstOp::Divide => "/",
+            AstOp::Modulus => "%",
+            AstOp::Reassign => "->",
+            AstOp::Equal => "==",
+            AstOp::NotEqual => "!=",
+            AstOp::Less => "<",
+            AstOp::LessEqual => "<=",
+            AstOp::Greater => ">",
+            AstOp::GreaterEqual => ">=",
+            AstOp::Negate => "-",
+            AstOp::Not => "!",
+        }
+    }
+}
+
+/// Each node prints its own children, since what goes between them depends on the node,
+/// so none of these walk the tree on their own
+impl Visitor for Unparser {
+    fn visit_stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::Empty { span: _ } => {}
+            Stmt::Binding { span: _, mutable, name, name_span: _, initializer, annotation } => {
+                if *mutable {
+                    self.output.push_str("mut ");
+                }
+                self.output.push_str(name);
+                if let Some(annotation) = annotation {
+                    self.output.push_str(": ");
+                    self.visit_type_expr(annotation);
+                }
+                self.output.push_str(" = ");
+                if let Some(initializer) = initializer {
+                    self.visit_expr(initializer);
+                }
+            }
+            Stmt::Expression { span: _, expr } => self.visit_expr(expr),
+            Stmt::Block { span, body } => self.block(body, span.stop),
+            Stmt::Function { span, name, name_span: _, parameters, body } => {
+                let parameters: Vec<&str> = parameters
+                    .iter()
+                    .map(|parameter| parameter.name.as_str())
+                    .collect();
+                self.output.push_str(&format!("function {}({}) ", name, parameters.join(", ")));
+                self.block(body, span.stop);
+            }
+            Stmt::Return { span: _, value } => {
+                self.output.push_str("return");
+                if let Some(value) = value {
+                    self.output.push(' ');
+                    self.visit_expr(value);
+                }
+            }
+            Stmt::Struct { span, name, fields } => {
+                self.output.push_str(&format!("struct {} ", name));
+                if self.is_empty(fields.len(), span.stop) {
+                    self.output.push_str("{}");
+                    return;
+                }
+
+                let bound = self.open(span.stop);
+                for field in fields {
+                    self.item(field.span.start);
+                    self.output.push_str(&format!("{}: ", field.name));
+                    self.visit_type_expr(&field.annotation);
+                    self.trailing(field.annotation.span().stop);
+                }
+                self.close(span.stop, bound);
+            }
+            Stmt::Enum { span, name, variants } => {
+                self.output.push_str(&format!("enum {} ", name));
+                if self.is_empty(variants.len(), span.stop) {
+                    self.output.push_str("{}");
+                    return;
+                }
+
+                let bound = self.open(span.stop);
+                for variant in variants {
+                    self.item(variant.span.start);
+                    self.output.push_str(&variant.name);
+                    if !variant.payload.is_empty() {
+                        self.output.push('(');
+                        for (i, type_expr) in variant.payload.iter().enumerate() {
+                            if i > 0 {
+                                self.output.push_str(", ");
+                            }
+                            self.visit_type_expr(type_expr);
+                        }
+                        self.output.push(')');
+                    }
+                    let end = variant.payload.last().map_or(variant.span.stop, |t| t.span().stop);
+                    self.trailing(end);
+                }
+                self.close(span.stop, bound);
+            }
+            Stmt::If { span, condition, body, else_branch } => {
+                self.output.push_str("if ");
+                self.condition(condition);
+                self.output.push(' ');
+                let end = else_branch.as_ref().map_or(span.stop, |branch| branch.span().start);
+                self.block(body, end);
+                if let Some(else_branch) = else_branch {
+                    self.output.push_str(" else ");
+                    self.visit_stmt(else_branch);
+                }
+            }
+            Stmt::While { span, condition, body } => {
+                self.output.push_str("while ");
+                self.condition(condition);
+                self.output.push(' ');
+                self.block(body, span.stop);
+            }
+            Stmt::Break { span: _ } => self.output.push_str("break"),
+            Stmt::Continue { span: _ } => self.output.push_str("continue"),
+        }
+    }
+
+    fn visit_expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Empty { span: _ } => {}
+            Expr::Float { span: _, value } => self.output.push_str(&Self::float(*value)),
+            Expr::Integer { span: _, value } => self.output.push_str(&value.to_string()),
+            Expr::String { span: _, value } => self.output.push_str(&Self::string(value)),
+            Expr::Bool { span: _, value } => self.output.push_str(&value.to_string()),
+            Expr::Symbol { span: _, name } => self.output.push_str(name),
+            Expr::Grouping { span: _, inner } => self.visit_expr(inner),
+
+            Expr::Binary { span: _, lhs, rhs, op } => {
+                // An operand of equal precedence only needs parentheses on the side the
+                // operator doesn't group towards, e.g. `a - (b - c)` but `(a - b) - c`
+                let precedence = op.precedence();
+                let (lhs_parens, rhs_parens) = match op.associativity() {
+                    Associativity::Left =>
+                        (Self::precedence(lhs) < precedence, Self::precedence(rhs) <= precedence),
+                    Associativity::Right =>
+                        (Self::precedence(lhs) <= precedence, Self::precedence(rhs) < precedence),
+                };
+
+                self.operand(lhs, lhs_parens);
+                self.output.push_str(&format!(" {} ", Self::operator(op)));
+                self.operand(rhs, rhs_parens);
+            }
+            Expr::Unary { span: _, op, operand } => {
+                self.output.push_str(Self::operator(op));
+                self.operand(operand, Self::precedence(operand) < op.precedence());
+            }
+            Expr::Assignment { span: _, lhs, rhs } => {
+                let precedence = AstOp::Reassign.precedence();
+                self.operand(lhs, Self::precedence(lhs) <= precedence);
+                self.output.push_str(" -> ");
+                self.visit_expr(rhs);
+            }
+
+            Expr::FunctionCall { span: _, callee, arguments } => {
+                self.operand(callee, Self::precedence(callee) < i8::MAX);
+                self.output.push('(');
+                self.list(arguments);
+                self.output.push(')');
+            }
+            Expr::Array { span: _, elements } => {
+                self.output.push('[');
+                self.list(elements);
+                self.output.push(']');
+            }
+            Expr::Index { span: _, target, index } => {
+                self.operand(target, Self::precedence(target) < i8::MAX);
+                self.output.push('[');
+                self.nested(index);
+                self.output.push(']');
+            }
+            Expr::Struct { span: _, name, fields } => {
+                if !self.struct_literals {
+                    self.operand(expr, true);
+                    return;
+                }
+
+                self.output.push_str(name);
+                if fields.is_empty() {
+                    self.output.push_str(" {}");
+                    return;
+                }
+                self.output.push_str(" { ");
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.output.push_str(&format!("{}: ", field.name));
+                    self.nested(&field.value);
+                }
+                self.output.push_str(" }");
+            }
+            Expr::Field { span: _, target, name } => {
+                self.operand(target, Self::precedence(target) < i8::MAX);
+                self.output.push('.');
+                self.output.push_str(name);
+            }
+            Expr::Match { span, scrutinee, arms } => {
+                self.output.push_str("match ");
+                self.condition(scrutinee);
+                if self.is_empty(arms.len(), span.stop) {
+                    self.output.push_str(" {}");
+                    return;
+                }
+
+                self.output.push(' ');
+                let bound = self.open(span.stop);
+                for arm in arms {
+                    self.item(arm.pattern.span().start);
+                    self.visit_pattern(&arm.pattern);
+                    self.output.push_str(" => ");
+                    self.nested(&arm.body);
+                    self.trailing(arm.body.span().stop);
+                }
+                self.close(span.stop, bound);
+            }
+        }
+    }
+
+    fn visit_pattern(&mut self, pattern: &Pattern) {
+        match pattern {
+            Pattern::Empty { span: _ } => {}
+            Pattern::Wildcard { span: _ } => self.output.push('_'),
+            Pattern::Symbol { span: _, name } => self.output.push_str(name),
+            Pattern::Variant { span: _, name, fields } => {
+                self.output.push_str(name);
+                self.output.push('(');
+                for (i, field) in fields.iter().enumerate() {
+                    if i > 0 {
+                        self.output.push_str(", ");
+                    }
+                    self.visit_pattern(field);
+                }
+                self.output.push(')');
+            }
+            Pattern::Integer { span: _, value } => self.output.push_str(&value.to_string()),
+            Pattern::String { span: _, value } => self.output.push_str(&Self::string(value)),
+            Pattern::Bool { span: _, value } => self.output.push_str(&value.to_string()),
+        }
+    }
+
+    fn visit_type_expr(&mut self, type_expr: &TypeExpr) {
+        match type_expr {
+            TypeExpr::Empty { span: _ } => {}
+            TypeExpr::Named { span: _, name } => self.output.push_str(name),
+            TypeExpr::Array { span: _, element } => {
+                self.output.push('[');
+                self.visit_type_expr(element);
+                self.output.push(']');
+            }
+        }
+    }
+}
+
+impl Unparser {
+    pub fn unparse(&mut self, tree: &Vec<Stmt>) {
+        self.statements(tree);
+        self.closing(usize::MAX);
+        if !self.output.is_empty() {
+            self.output.push('\n');
+        }
+    }
+
+    /// Prints each statement on its own line
+    fn statements(&mut self, body: &Vec<Stmt>) {
+        let body: Vec<&Stmt> = body
+            .iter()
+            .filter(|stmt| !matches!(stmt, Stmt::Empty { span: _ }))
+            .collect();
+        for (i, stmt) in body.iter().enumerate() {
+            self.item(stmt.span().start);
+            self.visit_stmt(stmt);
+
+            // A comment after statements separated by ';' belongs to the last of them
+            let bound = match body.get(i + 1) {
+                Some(next) => std::mem::replace(&mut self.bound, next.span().start),
+                None => self.bound,
+            };
+            self.trailing(stmt.span().stop);
+            self.bound = bound;
+        }
+    }
+
+    /// Prints the statements of a block whose closing `}` is before `end`
+    fn block(&mut self, body: &Vec<Stmt>, end: usize) {
+        let statements = body
+            .iter()
+            .filter(|stmt| !matches!(stmt, Stmt::Empty { span: _ }))
+            .count();
+        if self.is_empty(statements, end) {
+            self.output.push_str("{}");
+            return;
+        }
+
+        let bound = self.open(end);
+        self.statements(body);
+        self.close(end, bound);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::tests::parse;
+
+    fn unparse(tree: &Vec<Stmt>) -> String {
+        let mut unparser = Unparser::new();
+        unparser.unparse(tree);
+        return unparser.output;
+    }
+
+    /// Returns the debug output of a tree with every span and grouping left out, so that trees
+    /// parsed from differently laid out or parenthesized source can be compared
+    fn shape(tree: &Vec<Stmt>) -> String {
+        let debug = format!("{:?}", tree);
+        let mut shape = String::new();
+        let mut rest = debug.as_str();
+        while let Some(start) = rest.find("Span {") {
+            let stop = start + rest[start..].find('}').unwrap();
+            shape.push_str(&rest[..start]);
+            rest = &rest[stop + 1..];
+        }
+        shape.push_str(rest);
+
+        const GROUPING: &'static str = "Grouping { span: , inner: ";
+        while let Some(start) = shape.find(GROUPING) {
+            let inner = start + GROUPING.len();
+            let stop = inner + closing(&shape[inner..]);
+            let grouped = shape[inner..stop].trim_end().to_string();
+            shape.replace_range(start..=stop, &grouped);
+        }
+        return shape;
+    }
+
+    /// Returns where the `}`, `)` or `]` closing what `debug` is inside of is
+    fn closing(debug: &str) -> usize {
+        let mut depth = 0usize;
+        let mut quoted = false;
+        let mut chars = debug.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' if quoted => {
+                    chars.next();
+                }
+                '"' => quoted = !quoted,
+                '{' | '(' | '[' if !quoted => depth += 1,
+                '}' | ')' | ']' if !quoted => {
+                    if depth == 0 {
+                        return i;
+                    }
+                    depth -= 1;
+                }
+                _ => {}
+            }
+        }
+        unreachable!("{} isn't closed", debug);
+    }
+
+    /// Checks that `source` is printed as `expected`, and that parsing that gives back the
+    /// same tree, up to the parentheses the source had
+    fn round_trip(source: &str, expected: &str) {
+        let tree = parse(source);
+        let printed = unparse(&tree);
+        assert_eq!(printed, expected);
+        assert_eq!(shape(&parse(&printed)), shape(&tree), "{} parses differently", printed);
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        round_trip("x = (a - b) - c", "x = a - b - c\n");
+        round_trip("x = a - (b - c)", "x = a - (b - c)\n");
+        round_trip("x = (a + b) * c", "x = (a + b) * c\n");
+        round_trip("x = a + (b * c)", "x = a + b * c\n");
+        round_trip("x = (a / b) % (c * d)", "x = a / b % (c * d)\n");
+        round_trip("x = (a < b) == (c >= d)", "x = a < b == (c >= d)\n");
+        round_trip("x = a == (b != c)", "x = a == (b != c)\n");
+        round_trip("a -> (b -> c)", "a -> b -> c\n");
+        round_trip("(a -> b) -> c", "(a -> b) -> c\n");
+        round_trip("a -> (b + c)", "a -> b + c\n");
+        round_trip("x = (f(a + b)).c[(d - e)]", "x = f(a + b).c[d - e]\n");
+        round_trip("x = (a + b)[c]", "x = (a + b)[c]\n");
+
+        // Parentheses the tree doesn't need are dropped, however many the source had
+        round_trip("x = ((a))", "x = a\n");
+        round_trip("((f))((a))", "f(a)\n");
+    }
+
+    #[test]
+    fn unary_operators() {
+        round_trip("x = -(a + b)", "x = -(a + b)\n");
+        round_trip("x = (-a) + b", "x = -a + b\n");
+        round_trip("x = !(a == b)", "x = !(a == b)\n");
+        round_trip("x = (!a) == b", "x = !a == b\n");
+        round_trip("x = -(-a)", "x = --a\n");
+        round_trip("x = !(!a)", "x = !!a\n");
+        round_trip("x = -(a.b)", "x = -a.b\n");
+        round_trip("x = (-a).b", "x = (-a).b\n");
+        round_trip("x = -(f(a))", "x = -f(a)\n");
+        round_trip("x = -1.5 * -2", "x = -1.5 * -2\n");
+    }
+
+    #[test]
+    fn struct_literals_in_conditions() {
+        round_trip("if (P { x: 1 }).x == a {\n}", "if (P { x: 1 }).x == a {}\n");
+        round_trip("while (P { x: 1 }) == a {\n}", "while (P { x: 1 }) == a {}\n");
+        round_trip(
+            "m = match (P { x: 1 }) {\n    _ => 1\n}",
+            "m = match (P { x: 1 }) {\n    _ => 1\n}\n"
+        );
+
+        // Delimiters and places other than a condition allow them again
+        round_trip("if f(P { x: 1 }) {\n}", "if f(P { x: 1 }) {}\n");
+        round_trip("if a[(P {}).x] {\n}", "if a[P {}.x] {}\n");
+        round_trip("p = (P { x: 1, y: (Q {}) })", "p = P { x: 1, y: Q {} }\n");
+        round_trip(
+            "m = match a {\n    _ => (P { x: 1 })\n}",
+            "m = match a {\n    _ => P { x: 1 }\n}\n"
+        );
+    }
+
+    #[test]
+    fn match_arms() {
+        let source =
+            "area = match shape {
+    Circle(r) => 3.14 * r * r
+    Rect(w, h) => w * h
+    Nested(Some(1), _) => 0
+    \"square\" => 1
+    true => 2
+    other => match other {
+        _ => 3
+    }
+}
+";
+        round_trip(source, source);
+        round_trip("m = match a {\n}", "m = match a {}\n");
+    }
+
+    #[test]
+    fn strings_with_escapes() {
+        round_trip(r#"s = "a\"b\\c\nd\te\r\0""#, "s = \"a\\\"b\\\\c\\nd\\te\\r\\0\"\n");
+        round_trip(r#"s = "\u{7F} and é""#, "s = \"\\u{7F} and é\"\n");
+        round_trip(r#"s = "\u{48}i""#, "s = \"Hi\"\n");
+    }
+
+    #[test]
+    fn statements() {
+        let source =
+            "mut total: [int] = [1, 2]
+function add(a, b) {
+    return a + b
+}
+struct Point {
+    x: float
+    y: float
+}
+enum Shape {
+    Circle(float)
+    Empty
+}
+if a {
+    total[0] -> add(1, 2)
+} else if b {
+    return
+} else {
+    while true {
+        break
+        continue
+    }
+}
+{
+    p = Point { x: 1.0, y: 2.0 }
+}
+";
+        round_trip(source, source);
+    }
+}
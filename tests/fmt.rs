@@ -0,0 +1,45 @@
+use std::{ fs, path::PathBuf, process::Command };
+
+/// Writes `contents` to a file of its own in the temporary directory
+fn write(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("chao-{}-{}.chao", name, std::process::id()));
+    fs::write(&path, contents).unwrap();
+    return path;
+}
+
+fn fmt(args: &[&str], path: &PathBuf) -> i32 {
+    let status = Command::new(env!("CARGO_BIN_EXE_ChaoRS"))
+        .arg("fmt")
+        .args(args)
+        .arg(path)
+        .output()
+        .unwrap()
+        .status;
+    return status.code().unwrap();
+}
+
+#[test]
+fn check_leaves_unformatted_files_alone() {
+    let source = "x   =  1 // one\n\n\ny = x*2\n";
+    let path = write("check", source);
+
+    assert_eq!(fmt(&["--check"], &path), 1);
+    assert_eq!(fs::read_to_string(&path).unwrap(), source);
+
+    assert_eq!(fmt(&[], &path), 0);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "x = 1 // one\n\ny = x * 2\n");
+    assert_eq!(fmt(&["--check"], &path), 0);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn files_with_errors_are_not_written() {
+    let source = "x = (1\n";
+    let path = write("errors", source);
+
+    assert_eq!(fmt(&[], &path), 1);
+    assert_eq!(fs::read_to_string(&path).unwrap(), source);
+
+    fs::remove_file(&path).unwrap();
+}